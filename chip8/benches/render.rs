@@ -0,0 +1,50 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use chip8::filters::Filters;
+use chip8::palette::CLASSIC;
+use core::{Display, EdgeMode};
+
+const SPRITE: [u8; 5] = [0xF0, 0x90, 0xF0, 0x90, 0x90];
+
+fn frame(display: &mut Display, frame: usize) {
+    display.draw_sprite(frame % 64, 12, &SPRITE, EdgeMode::Wrap);
+    display.draw_sprite((frame + 1) % 64, 12, &SPRITE, EdgeMode::Wrap);
+}
+
+/// The window's render path: every emulated frame goes through the filters into the texture data
+fn filters(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filters");
+    let mut filters = Filters::new(Display::WIDTH, Display::HEIGHT);
+    let (width, height) = filters.output_size();
+    let mut out = vec![0; width * height * 3];
+
+    group.bench_function("full", |b| {
+        let mut display = Display::new();
+        let mut n = 0;
+
+        b.iter(|| {
+            frame(&mut display, n);
+            display.invalidate();
+            filters.apply(&display, &CLASSIC, 1.0, &mut out);
+            display.clear_dirty();
+            n += 1;
+        });
+    });
+
+    group.bench_function("dirty_rows", |b| {
+        let mut display = Display::new();
+        let mut n = 0;
+
+        b.iter(|| {
+            frame(&mut display, n);
+            filters.apply(&display, &CLASSIC, 1.0, &mut out);
+            display.clear_dirty();
+            n += 1;
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, filters);
+criterion_main!(benches);
@@ -1,40 +1,40 @@
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::AudioSubsystem;
 
-pub struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+use crate::timeline::{self, SoundClock, SoundTimeline};
+use crate::tone::{AudioSettings, Tone};
+
+pub struct Beeper {
+    pub tone: Tone,
+    timeline: SoundTimeline,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Beeper {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        for x in out.iter_mut() {
-            *x = self.volume;
-
-            if self.phase > 0.5 {
-                *x = -*x;
-            }
-
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+        self.timeline.render(out, &mut self.tone);
     }
 }
 
-pub fn create_audio_device(audio_subsystem: &AudioSubsystem) -> Result<AudioDevice<SquareWave>, String> {
+pub fn create_audio_device(audio_subsystem: &AudioSubsystem, settings: AudioSettings) -> Result<(AudioDevice<Beeper>, SoundClock), String> {
     let desired_spec = AudioSpecDesired {
         freq: Some(44100),
         channels: Some(1),
         samples: None,
     };
 
-    audio_subsystem.open_playback(None, &desired_spec, |spec| {
-        SquareWave {
-            phase_inc: 440.0 / spec.freq as f32,
-            phase: 0.0,
-            volume: 0.25,
+    let mut clock = None;
+
+    let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+        let (sound_clock, timeline) = timeline::channel(spec.freq as u32);
+        clock = Some(sound_clock);
+
+        Beeper {
+            tone: Tone::new(spec.freq as u32, settings),
+            timeline,
         }
-    })
-}
\ No newline at end of file
+    })?;
+
+    Ok((device, clock.expect("audio callback was not created")))
+}
@@ -0,0 +1,198 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sdl2::pixels::Color;
+
+use crate::database::Database;
+use crate::text::{self, Surface, LINE_HEIGHT};
+use crate::ui::Menu;
+
+pub const ROM_EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+
+const INFO_LINES: usize = 5;
+const INFO_COLOR: Color = Color::RGB(170, 200, 170);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+    Parent(PathBuf),
+    Dir(PathBuf),
+    Rom(PathBuf),
+}
+
+pub struct Browser {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    menu: Menu,
+    info: Vec<String>,
+}
+
+impl Browser {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let dir = fs::canonicalize(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let mut dirs = Vec::new();
+        let mut roms = Vec::new();
+
+        for entry in fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?.flatten() {
+            let path = entry.path();
+
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_rom(&path) {
+                roms.push(path);
+            }
+        }
+
+        dirs.sort();
+        roms.sort();
+
+        let entries: Vec<Entry> = dir.parent().map(|parent| Entry::Parent(parent.to_path_buf())).into_iter()
+            .chain(dirs.into_iter().map(Entry::Dir))
+            .chain(roms.into_iter().map(Entry::Rom))
+            .collect();
+
+        let names = entries.iter().map(|entry| match entry {
+            Entry::Parent(_) => "../".to_string(),
+            Entry::Dir(path) => format!("{}/", file_name(path)),
+            Entry::Rom(path) => file_name(path),
+        }).collect();
+
+        let menu = Menu::new(&dir.to_string_lossy(), names);
+        Ok(Self { dir, entries, menu, info: Vec::new() })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn select_next(&mut self) {
+        self.menu.select_next();
+        self.info.clear();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.menu.select_previous();
+        self.info.clear();
+    }
+
+    pub fn selected_rom(&self) -> Option<&Path> {
+        match self.entries.get(self.menu.selected()?)? {
+            Entry::Rom(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn selected_dir(&self) -> Option<&Path> {
+        match self.entries.get(self.menu.selected()?)? {
+            Entry::Parent(path) | Entry::Dir(path) => Some(path),
+            Entry::Rom(_) => None,
+        }
+    }
+
+    pub fn parent(&self) -> Option<&Path> {
+        self.dir.parent()
+    }
+
+    pub fn update_info(&mut self, database: &Database, width: usize) {
+        if !self.info.is_empty() {
+            return;
+        }
+
+        let program = match self.selected_rom().and_then(|path| fs::read(path).ok()) {
+            Some(program) => program,
+            None => return,
+        };
+
+        let max_chars = text::max_chars(width);
+        let mut info = vec![format!("{} bytes", program.len())];
+
+        if let Some((entry, rom)) = database.lookup(&program) {
+            info = vec![entry.title.clone()];
+
+            let mut details = entry.authors.join(", ");
+
+            if let Some(release) = &entry.release {
+                details = if details.is_empty() { release.clone() } else { format!("{} ({})", details, release) };
+            }
+
+            if let Some(rom) = rom.filter(|rom| !rom.platforms.is_empty()) {
+                details = format!("{} - {}", details, rom.platforms.join(", "));
+            }
+
+            info.extend(text::wrap(&details, max_chars));
+            info.extend(text::wrap(entry.description.as_deref().unwrap_or_default(), max_chars));
+        }
+
+        info.truncate(INFO_LINES);
+        self.info = info;
+    }
+
+    pub fn draw(&self, surface: &mut Surface) {
+        let (width, height) = (surface.width(), surface.height());
+        let top = height - INFO_LINES * LINE_HEIGHT - 2;
+
+        self.menu.draw(&mut surface.top(top));
+        surface.fill_rect(0, top, width, height - top, Color::BLACK);
+
+        for (i, line) in self.info.iter().enumerate() {
+            surface.draw_text(4, top + 2 + i * LINE_HEIGHT, line, INFO_COLOR);
+        }
+    }
+}
+
+pub fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy()).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip8-browser-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("games")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_is_rom() {
+        assert!(is_rom(Path::new("pong.ch8")));
+        assert!(is_rom(Path::new("dir/CAR.SC8")));
+        assert!(is_rom(Path::new("x.xo8")));
+        assert!(!is_rom(Path::new("notes.txt")));
+        assert!(!is_rom(Path::new("ch8")));
+    }
+
+    #[test]
+    fn test_entries() {
+        let dir = temp_dir("entries");
+        fs::write(dir.join("b.ch8"), [0x00, 0xE0]).unwrap();
+        fs::write(dir.join("a.sc8"), [0x00, 0xE0]).unwrap();
+        fs::write(dir.join("readme.txt"), "").unwrap();
+
+        let mut browser = Browser::open(&dir).unwrap();
+        assert_eq!(browser.selected_dir(), dir.canonicalize().unwrap().parent());
+
+        browser.select_next();
+        assert!(browser.selected_dir().unwrap().ends_with("games"));
+
+        browser.select_next();
+        assert!(browser.selected_rom().unwrap().ends_with("a.sc8"));
+
+        browser.select_next();
+        assert!(browser.selected_rom().unwrap().ends_with("b.ch8"));
+
+        browser.update_info(&Database::default(), 200);
+        assert_eq!(browser.info, ["2 bytes"]);
+
+        browser.select_next();
+        assert!(browser.selected_dir().is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use core::Display;
+use gif::{Encoder, Frame, Repeat};
+use png::{BitDepth, ColorType};
+
+pub fn next_filename(dir: &Path, extension: &str) -> PathBuf {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let mut path = dir.join(format!("chip8-{}.{}", secs, extension));
+    let mut index = 2;
+
+    while path.exists() {
+        path = dir.join(format!("chip8-{}-{}.{}", secs, index, extension));
+        index += 1;
+    }
+
+    path
+}
+
+pub fn save_screenshot(path: &Path, display: &Display, colors: [[u8; 3]; 2], scale: usize) -> Result<(), String> {
+    let (width, height) = (display.width() * scale, display.height() * scale);
+    let mut image = vec![0; width * height * 3];
+    display.render_rgb24(&mut image, colors, scale);
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&image).map_err(|e| e.to_string())
+}
+
+pub enum Recording {
+    Gif(GifRecorder),
+    Raw(RawRecorder),
+}
+
+impl Recording {
+    pub fn push_frame(&mut self, display: &Display, colors: [[u8; 3]; 2]) -> Result<(), String> {
+        match self {
+            Self::Gif(recorder) => recorder.push_frame(display, colors),
+            Self::Raw(recorder) => recorder.push_frame(display, colors),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            Self::Gif(recorder) => recorder.finish(),
+            Self::Raw(recorder) => recorder.finish(),
+        }
+    }
+}
+
+/// A display and the `[off, on]` colors it's rendered with
+type Screen = (Display, [[u8; 3]; 2]);
+
+fn same_screen(screen: &Screen, display: &Display, colors: [[u8; 3]; 2]) -> bool {
+    screen.0.pixel_rows() == display.pixel_rows() && screen.1 == colors
+}
+
+pub struct GifRecorder {
+    encoder: Encoder<BufWriter<File>>,
+    width: usize,
+    height: usize,
+    scale: usize,
+    fps: u32,
+    pending: Option<Screen>,
+    pending_frames: u32,
+}
+
+impl GifRecorder {
+    pub fn new(path: &Path, width: usize, height: usize, scale: usize, fps: u32) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut encoder = Encoder::new(BufWriter::new(file), (width * scale) as u16, (height * scale) as u16, &[])
+            .map_err(|e| e.to_string())?;
+        encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            scale,
+            fps,
+            pending: None,
+            pending_frames: 0,
+        })
+    }
+
+    pub fn push_frame(&mut self, display: &Display, colors: [[u8; 3]; 2]) -> Result<(), String> {
+        // A screen that doesn't change for longer than the longest GIF delay is split into several frames
+        let max_frames = u16::MAX as u32 * self.fps / 100;
+
+        if self.pending.as_ref().is_some_and(|pending| same_screen(pending, display, colors)) && self.pending_frames < max_frames {
+            self.pending_frames += 1;
+            return Ok(());
+        }
+
+        self.flush()?;
+        self.pending = Some((display.clone(), colors));
+        self.pending_frames = 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        let (display, colors) = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        let mut scaled = vec![0; self.width * self.height * self.scale * self.scale * 3];
+        display.render_rgb24(&mut scaled, colors, self.scale);
+        let (width, height) = ((self.width * self.scale) as u16, (self.height * self.scale) as u16);
+
+        let mut frame = match index_colors(&scaled) {
+            Some((indices, palette)) => Frame::from_palette_pixels(width, height, indices, palette, None),
+            None => Frame::from_rgb_speed(width, height, &scaled, 10),
+        };
+
+        // GIF delays are in hundredths of a second
+        frame.delay = ((self.pending_frames * 100 + self.fps / 2) / self.fps).clamp(1, u16::MAX as u32) as u16;
+        self.encoder.write_frame(&frame).map_err(|e| e.to_string())
+    }
+}
+
+pub struct RawRecorder {
+    writer: BufWriter<File>,
+    scale: usize,
+    last: Option<Screen>,
+    frame: Vec<u8>,
+}
+
+impl RawRecorder {
+    pub fn new(path: &Path, width: usize, height: usize, scale: usize) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            writer: BufWriter::new(file),
+            scale,
+            last: None,
+            frame: vec![0; width * height * scale * scale * 3],
+        })
+    }
+
+    pub fn push_frame(&mut self, display: &Display, colors: [[u8; 3]; 2]) -> Result<(), String> {
+        if !self.last.as_ref().is_some_and(|last| same_screen(last, display, colors)) {
+            display.render_rgb24(&mut self.frame, colors, self.scale);
+            self.last = Some((display.clone(), colors));
+        }
+
+        self.writer.write_all(&self.frame).map_err(|e| e.to_string())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+fn index_colors(rgb: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut indices = Vec::with_capacity(rgb.len() / 3);
+
+    for pixel in rgb.chunks_exact(3) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+
+        let index = match palette.iter().position(|c| *c == color) {
+            Some(index) => index,
+            None if palette.len() < 256 => {
+                palette.push(color);
+                palette.len() - 1
+            }
+            None => return None,
+        };
+
+        indices.push(index as u8);
+    }
+
+    Some((indices, palette.concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_raw_recorder() -> Result<(), String> {
+        let path = std::env::temp_dir().join(format!("chip8-capture-{}.rgb", std::process::id()));
+        let mut recorder = RawRecorder::new(&path, Display::WIDTH, Display::HEIGHT, 2)?;
+        let mut display = Display::new();
+        let colors = [[0; 3], [255, 128, 0]];
+
+        recorder.push_frame(&display, colors)?;
+        display.toggle(1, 0);
+        recorder.push_frame(&display, colors)?;
+        recorder.finish()?;
+
+        let frames = fs::read(&path).map_err(|e| e.to_string())?;
+        let frame_size = Display::WIDTH * Display::HEIGHT * 2 * 2 * 3;
+        assert_eq!(frames.len(), 2 * frame_size);
+        assert_eq!(frames[6..9], [0, 0, 0]);
+        assert_eq!(frames[frame_size + 6..frame_size + 9], [255, 128, 0]);
+
+        fs::remove_file(&path).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_gif_long_still() -> Result<(), String> {
+        let path = std::env::temp_dir().join(format!("chip8-capture-{}.gif", std::process::id()));
+        let mut recorder = GifRecorder::new(&path, Display::WIDTH, Display::HEIGHT, 1, 100)?;
+        let display = Display::new();
+
+        for _ in 0..70_000 {
+            recorder.push_frame(&display, [[0; 3], [255; 3]])?;
+        }
+
+        recorder.finish()?;
+
+        let mut decoder = gif::DecodeOptions::new().read_info(File::open(&path).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        let mut delays = Vec::new();
+
+        while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+            delays.push(frame.delay);
+        }
+
+        assert_eq!(delays, [u16::MAX, (70_000 - u16::MAX as u32) as u16]);
+        fs::remove_file(&path).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_index_colors() {
+        let rgb = [0, 0, 0, 255, 255, 255, 0, 0, 0];
+        let (indices, palette) = index_colors(&rgb).unwrap();
+
+        assert_eq!(indices, [0, 1, 0]);
+        assert_eq!(palette, [0, 0, 0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_index_colors_overflow() {
+        let rgb: Vec<u8> = (0..=256u32).flat_map(|i| [(i % 256) as u8, (i / 256) as u8, 0]).collect();
+        assert!(index_colors(&rgb).is_none());
+    }
+}
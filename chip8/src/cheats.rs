@@ -0,0 +1,264 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use sdl2::keyboard::Keycode;
+
+use core::cheats::{Cheats, Comparison, Search};
+use crate::config::Config;
+use crate::text::Surface;
+use crate::ui::Menu;
+
+const CHEATS_DIR: &str = "cheats";
+const MAX_LISTED: usize = 100;
+
+/// Loads the cheats saved for `program` in the user config directory, or an empty list
+pub fn load(program: &[u8]) -> Result<Cheats, String> {
+    match Config::dir() {
+        Some(dir) => load_from(&dir.join(CHEATS_DIR), program),
+        None => Ok(Cheats::new(program)),
+    }
+}
+
+pub fn save(cheats: &Cheats) -> Result<(), String> {
+    let dir = Config::dir().ok_or("could not find the user config directory")?;
+    save_to(&dir.join(CHEATS_DIR), cheats)
+}
+
+fn path(dir: &Path, rom_hash: u64) -> PathBuf {
+    dir.join(format!("{:016x}.cht", rom_hash))
+}
+
+fn load_from(dir: &Path, program: &[u8]) -> Result<Cheats, String> {
+    let empty = Cheats::new(program);
+    let path = path(dir, empty.rom_hash);
+
+    let cheats: Cheats = match fs::read_to_string(&path) {
+        Ok(contents) => contents.parse().map_err(|e: core::Error| format!("{}: {}", path.display(), e))?,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(empty),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+
+    if !cheats.matches_program(program) {
+        return Err(format!("{}: cheats are for a different ROM", path.display()));
+    }
+
+    Ok(cheats)
+}
+
+fn save_to(dir: &Path, cheats: &Cheats) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    fs::write(path(dir, cheats.rom_hash), cheats.to_string()).map_err(|e| e.to_string())
+}
+
+/// Cheat list and memory search shown in the window. The search survives closing the panel, so the game can run
+/// between filters.
+pub struct CheatPanel {
+    pub cheats: Cheats,
+    search: Option<Search>,
+    input: String,
+    selected: usize,
+    modified: bool,
+}
+
+impl CheatPanel {
+    pub fn new(cheats: Cheats) -> Self {
+        Self { cheats, search: None, input: String::new(), selected: 0, modified: false }
+    }
+
+    /// Whether the cheats changed since the last call and should be saved
+    pub fn take_modified(&mut self) -> bool {
+        std::mem::take(&mut self.modified)
+    }
+
+    /// Handles a key press, returning a message to show
+    pub fn handle_key(&mut self, keycode: Keycode, memory: &[u8; 0x1000]) -> Option<String> {
+        let comparison = match keycode {
+            Keycode::Up => {
+                self.selected = (self.selected + self.len().max(1) - 1) % self.len().max(1);
+                return None;
+            }
+            Keycode::Down => {
+                self.selected = (self.selected + 1) % self.len().max(1);
+                return None;
+            }
+            Keycode::Backspace => {
+                self.input.pop();
+                return None;
+            }
+            Keycode::N => {
+                self.search = Some(Search::new(memory));
+                self.selected = 0;
+                return Some("Search started".to_string());
+            }
+            Keycode::Return | Keycode::KpEnter => return self.activate(memory),
+            Keycode::Delete => {
+                let cheat = self.cheats.cheats.get(self.selected)?.addr;
+                self.cheats.unpin(cheat);
+                self.modified = true;
+                return Some(format!("Removed cheat {:03X}", cheat));
+            }
+            Keycode::E => match self.value() {
+                Some(value) => Comparison::Equal(value),
+                None => return Some("Type a value first".to_string()),
+            },
+            Keycode::C => Comparison::Changed,
+            Keycode::U => Comparison::Unchanged,
+            Keycode::I => Comparison::Increased,
+            Keycode::D => Comparison::Decreased,
+            _ => {
+                if let Some(digit) = digit(keycode) {
+                    let input = format!("{}{}", self.input, digit);
+
+                    if input.parse::<u8>().is_ok() {
+                        self.input = input;
+                    }
+                }
+
+                return None;
+            }
+        };
+
+        if self.search.is_none() && matches!(comparison, Comparison::Equal(_)) {
+            self.search = Some(Search::new(memory));
+        }
+
+        let found = self.search.as_mut().map(|search| search.filter(memory, comparison));
+        self.selected = self.cheats.cheats.len();
+
+        Some(match found {
+            Some(found) => format!("{} found", found),
+            None => "Start a search with N first".to_string(),
+        })
+    }
+
+    pub fn draw(&self, surface: &mut Surface, memory: &[u8; 0x1000]) {
+        let mut title = match &self.search {
+            Some(search) => format!("Cheats: {} found", search.candidates().len()),
+            None => "Cheats".to_string(),
+        };
+
+        if !self.input.is_empty() {
+            title.push_str(&format!(", value {}", self.input));
+        }
+
+        let cheats = self.cheats.cheats.iter().map(|cheat| {
+            let state = if cheat.enabled { 'x' } else { ' ' };
+            format!("[{}] {:03X} = {} {}", state, cheat.addr, cheat.value, cheat.name)
+        });
+
+        let candidates = self.listed().iter().map(|&addr| {
+            let previous = self.search.as_ref().map_or(0, |search| search.previous(addr));
+            format!("    {:03X}   {} (was {})", addr, memory[addr as usize], previous)
+        });
+
+        let mut menu = Menu::new(&title, cheats.chain(candidates).collect());
+        menu.select(self.selected);
+        menu.draw(surface);
+    }
+
+    fn activate(&mut self, memory: &[u8; 0x1000]) -> Option<String> {
+        if let Some(cheat) = self.cheats.cheats.get_mut(self.selected) {
+            cheat.enabled = !cheat.enabled;
+            self.modified = true;
+            return Some(format!("Cheat {:03X} {}", cheat.addr, if cheat.enabled { "on" } else { "off" }));
+        }
+
+        let addr = *self.listed().get(self.selected - self.cheats.cheats.len())?;
+        let value = self.value().unwrap_or(memory[addr as usize]);
+        let count = self.cheats.cheats.len();
+        self.cheats.pin(addr, value);
+        self.selected += self.cheats.cheats.len() - count;
+        self.modified = true;
+        Some(format!("Pinned {:03X} to {}", addr, value))
+    }
+
+    fn listed(&self) -> &[u16] {
+        let candidates = self.search.as_ref().map_or(&[][..], Search::candidates);
+        &candidates[..candidates.len().min(MAX_LISTED)]
+    }
+
+    fn len(&self) -> usize {
+        self.cheats.cheats.len() + self.listed().len()
+    }
+
+    fn value(&self) -> Option<u8> {
+        self.input.parse().ok()
+    }
+}
+
+fn digit(keycode: Keycode) -> Option<u8> {
+    Some(match keycode {
+        Keycode::Num0 | Keycode::Kp0 => 0,
+        Keycode::Num1 | Keycode::Kp1 => 1,
+        Keycode::Num2 | Keycode::Kp2 => 2,
+        Keycode::Num3 | Keycode::Kp3 => 3,
+        Keycode::Num4 | Keycode::Kp4 => 4,
+        Keycode::Num5 | Keycode::Kp5 => 5,
+        Keycode::Num6 | Keycode::Kp6 => 6,
+        Keycode::Num7 | Keycode::Kp7 => 7,
+        Keycode::Num8 | Keycode::Kp8 => 8,
+        Keycode::Num9 | Keycode::Kp9 => 9,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: [u8; 2] = [0x12, 0x00];
+
+    fn type_keys(panel: &mut CheatPanel, keys: &[Keycode], memory: &[u8; 0x1000]) -> Option<String> {
+        keys.iter().map(|key| panel.handle_key(*key, memory)).last().flatten()
+    }
+
+    #[test]
+    fn test_search_and_pin() {
+        let mut memory = [0; 0x1000];
+        memory[0x3F0] = 3;
+        memory[0x3F8] = 3;
+
+        let mut panel = CheatPanel::new(Cheats::new(&PROGRAM));
+        assert_eq!(type_keys(&mut panel, &[Keycode::Num3, Keycode::E], &memory).as_deref(), Some("2 found"));
+
+        memory[0x3F0] = 2;
+        assert_eq!(type_keys(&mut panel, &[Keycode::D], &memory).as_deref(), Some("1 found"));
+
+        let message = type_keys(&mut panel, &[Keycode::Backspace, Keycode::Num9, Keycode::Return], &memory);
+        assert_eq!(message.as_deref(), Some("Pinned 3F0 to 9"));
+        assert!(panel.take_modified() && !panel.take_modified());
+
+        assert_eq!(panel.cheats.cheats[0].value, 9);
+        assert_eq!(type_keys(&mut panel, &[Keycode::Up, Keycode::Return], &memory).as_deref(), Some("Cheat 3F0 off"));
+    }
+
+    #[test]
+    fn test_needs_search() {
+        let memory = [0; 0x1000];
+        let mut panel = CheatPanel::new(Cheats::new(&PROGRAM));
+
+        assert_eq!(panel.handle_key(Keycode::C, &memory).as_deref(), Some("Start a search with N first"));
+        assert_eq!(panel.handle_key(Keycode::E, &memory).as_deref(), Some("Type a value first"));
+
+        // Values that don't fit in a byte are ignored
+        type_keys(&mut panel, &[Keycode::Num2, Keycode::Num5, Keycode::Num6], &memory);
+        assert_eq!(panel.value(), Some(25));
+    }
+
+    #[test]
+    fn test_cheat_files() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("chip8-cheats-{}", std::process::id()));
+        assert_eq!(load_from(&dir, &PROGRAM)?, Cheats::new(&PROGRAM));
+
+        let mut cheats = Cheats::new(&PROGRAM);
+        cheats.pin(0x3F0, 9);
+        save_to(&dir, &cheats)?;
+        assert_eq!(load_from(&dir, &PROGRAM)?, cheats);
+
+        fs::rename(path(&dir, cheats.rom_hash), path(&dir, Cheats::new(&[0x00, 0xE0]).rom_hash)).map_err(|e| e.to_string())?;
+        assert!(load_from(&dir, &[0x00, 0xE0]).unwrap_err().ends_with("cheats are for a different ROM"));
+
+        fs::remove_dir_all(&dir).map_err(|e| e.to_string())
+    }
+}
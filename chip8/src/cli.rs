@@ -0,0 +1,264 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use core::{Platform, Quirks};
+use crate::config::Config;
+use crate::dap::Transport;
+use crate::palette::Palettes;
+use crate::scheduler::DEFAULT_IPS;
+
+pub const DEFAULT_SCALE: u32 = 15;
+pub const DEFAULT_FRAMES: u64 = 600;
+pub const DEFAULT_CAPTURE_SCALE: u32 = 8;
+/// Keeps the 64x32 screen within the largest GIF dimensions and a reasonable PNG size
+pub const MAX_CAPTURE_SCALE: u32 = 64;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "A portable CHIP-8 emulator")]
+pub struct Args {
+    /// ROM file to run
+    pub rom: Option<PathBuf>,
+    /// CPU instructions per second
+    #[arg(long)]
+    pub ips: Option<u32>,
+    /// Limit the display refresh rate instead of following vsync
+    #[arg(long)]
+    pub fps: Option<u32>,
+    /// Window scale factor, instead of the remembered window size
+    #[arg(long)]
+    pub scale: Option<u32>,
+    /// Scale factor of screenshots and recordings (8 by default)
+    #[arg(long, value_name = "N")]
+    pub capture_scale: Option<u32>,
+    /// Color palette name
+    #[arg(long)]
+    pub palette: Option<String>,
+    /// Comma-separated quirks to enable, or disable with a `no-` prefix (shift, load-store, jump, vf-reset, clip)
+    #[arg(long)]
+    pub quirks: Option<String>,
+    /// Quirk preset (modern, vip, schip)
+    #[arg(long)]
+    pub platform: Option<String>,
+    /// RNG seed, decimal or 0x-prefixed hex
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<u64>,
+    /// Start paused
+    #[arg(long)]
+    pub paused: bool,
+    /// Start in fullscreen
+    #[arg(long)]
+    pub fullscreen: bool,
+    /// Start with the sound muted
+    #[arg(long)]
+    pub mute: bool,
+    /// Run without a window and print the screen after the given number of frames
+    #[arg(long)]
+    pub headless: bool,
+    /// Render the beeper output to a WAV file without a window
+    #[arg(long, value_name = "FILE")]
+    pub wav: Option<PathBuf>,
+    /// Number of frames to run with --headless or --wav
+    #[arg(long, default_value_t = DEFAULT_FRAMES)]
+    pub frames: u64,
+    /// Listen for a GDB remote debugger on the given localhost port
+    #[arg(long, value_name = "PORT")]
+    pub gdb: Option<u16>,
+    /// Run a Debug Adapter Protocol server over stdio, or on the given localhost port
+    #[arg(long, value_name = "PORT", num_args = 0..=1)]
+    pub dap: Option<Option<u16>>,
+    /// Rhai script to run alongside the ROM
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+    /// Profile the ROM and write a JSON report, or collapsed stacks if FILE doesn't end in .json
+    #[arg(long, value_name = "FILE")]
+    pub profile: Option<PathBuf>,
+    /// Record code coverage and merge it into FILE, with a listing and an LCOV tracefile next to it
+    #[arg(long, value_name = "FILE")]
+    pub coverage: Option<PathBuf>,
+    /// Replay a movie file with --headless, until it ends
+    #[arg(long, value_name = "FILE")]
+    pub movie: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub rom: Option<PathBuf>,
+    pub ips: u32,
+    pub fps: Option<u32>,
+    pub scale: Option<u32>,
+    pub capture_scale: usize,
+    pub palette: Option<String>,
+    pub quirks: Quirks,
+    pub seed: Option<u64>,
+    pub paused: bool,
+    pub fullscreen: bool,
+    pub mute: bool,
+    pub headless: bool,
+    pub wav: Option<PathBuf>,
+    pub frames: u64,
+    pub gdb: Option<u16>,
+    pub dap: Option<Transport>,
+    pub script: Option<PathBuf>,
+    pub profile: Option<PathBuf>,
+    pub coverage: Option<PathBuf>,
+    pub movie: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn resolve(self, config: &Config) -> Result<Settings, String> {
+        let platform: Platform = match self.platform.as_deref().or(config.platform.as_deref()) {
+            Some(name) => name.parse().map_err(|e: core::Error| e.to_string())?,
+            None => Platform::default(),
+        };
+
+        let mut quirks = platform.quirks();
+
+        for list in [&config.quirks, &self.quirks].into_iter().flatten() {
+            quirks.apply(list).map_err(|e| e.to_string())?;
+        }
+
+        let ips = self.ips.or(config.ips).unwrap_or(DEFAULT_IPS);
+        let scale = self.scale.or(config.scale);
+        let fps = self.fps.or(config.fps);
+
+        if ips == 0 || scale == Some(0) || fps == Some(0) {
+            return Err("ips, fps and scale must be greater than 0".to_string());
+        }
+
+        let capture_scale = self.capture_scale.or(config.capture_scale).unwrap_or(DEFAULT_CAPTURE_SCALE);
+
+        if !(1..=MAX_CAPTURE_SCALE).contains(&capture_scale) {
+            return Err(format!("capture scale must be between 1 and {}", MAX_CAPTURE_SCALE));
+        }
+
+        let rom_palettes = config.roms.values().filter_map(|rom| rom.palette.as_ref());
+
+        for name in self.palette.iter().chain(&config.palette).chain(rom_palettes) {
+            Palettes::check_name(config, name)?;
+        }
+
+        if self.gdb.is_some() && (self.headless || self.wav.is_some()) {
+            return Err("--gdb needs a window".to_string());
+        }
+
+        if self.dap.is_some() && (self.headless || self.wav.is_some() || self.gdb.is_some()) {
+            return Err("--dap needs a window and can't be combined with --gdb".to_string());
+        }
+
+        if self.script.is_some() && (self.gdb.is_some() || self.dap.is_some()) {
+            return Err("--script can't be combined with --gdb or --dap".to_string());
+        }
+
+        if self.profile.is_some() && (self.gdb.is_some() || self.dap.is_some() || self.script.is_some() || self.wav.is_some()) {
+            return Err("--profile can't be combined with --gdb, --dap, --script or --wav".to_string());
+        }
+
+        let tools = self.gdb.is_some() || self.dap.is_some() || self.script.is_some() || self.profile.is_some();
+
+        if self.coverage.is_some() && (tools || self.wav.is_some()) {
+            return Err("--coverage can't be combined with --gdb, --dap, --script, --profile or --wav".to_string());
+        }
+
+        if self.movie.is_some() && (!self.headless || self.script.is_some()) {
+            return Err("--movie needs --headless and can't be combined with --script".to_string());
+        }
+
+        Ok(Settings {
+            rom: self.rom,
+            ips,
+            fps,
+            scale,
+            capture_scale: capture_scale as usize,
+            palette: self.palette,
+            quirks,
+            seed: self.seed,
+            paused: self.paused || config.paused,
+            fullscreen: self.fullscreen || config.fullscreen,
+            mute: self.mute,
+            headless: self.headless || self.wav.is_some(),
+            wav: self.wav,
+            frames: self.frames,
+            gdb: self.gdb,
+            dap: self.dap.map(|port| port.map_or(Transport::Stdio, Transport::Socket)),
+            script: self.script,
+            profile: self.profile,
+            coverage: self.coverage,
+            movie: self.movie,
+        })
+    }
+}
+
+fn parse_seed(value: &str) -> Result<u64, String> {
+    crate::parse_seed(value).ok_or_else(|| format!("invalid seed: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(args: &[&str], config: &str) -> Result<Settings, String> {
+        let args = Args::try_parse_from([&["chip8"], args].concat()).map_err(|e| e.to_string())?;
+        args.resolve(&Config::parse(config)?)
+    }
+
+    #[test]
+    fn test_defaults() -> Result<(), String> {
+        let settings = resolve(&[], "")?;
+
+        assert_eq!(settings.ips, DEFAULT_IPS);
+        assert_eq!(settings.scale, None);
+        assert_eq!(settings.capture_scale, DEFAULT_CAPTURE_SCALE as usize);
+        assert_eq!(settings.quirks, Quirks::default());
+        assert!(settings.rom.is_none() && !settings.headless);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_overrides_config() -> Result<(), String> {
+        let config = "ips = 1000\nscale = 10\ncapture_scale = 4\nplatform = \"vip\"\nquirks = \"no-clip\"";
+        let settings = resolve(&["game.ch8", "--ips", "2000", "--quirks", "no-shift", "--seed", "0x10"], config)?;
+        assert_eq!(settings.capture_scale, 4);
+        assert_eq!(resolve(&["--capture-scale", "2"], config)?.capture_scale, 2);
+
+        assert_eq!(settings.rom, Some(PathBuf::from("game.ch8")));
+        assert_eq!(settings.ips, 2000);
+        assert_eq!(settings.scale, Some(10));
+        assert_eq!(settings.seed, Some(0x10));
+        assert_eq!(settings.quirks, Quirks { shift_vy: false, clip: false, ..Quirks::VIP });
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(resolve(&["--platform", "nes"], "").is_err());
+        assert!(resolve(&["--quirks", "bogus"], "").is_err());
+        assert!(resolve(&["--seed", "xyz"], "").is_err());
+        assert!(resolve(&["--ips", "0"], "").is_err());
+        assert!(resolve(&["--capture-scale", "0"], "").is_err());
+        assert!(resolve(&[], "capture_scale = 65").is_err());
+        assert!(resolve(&["--gdb", "1234", "--headless"], "").is_err());
+        assert!(resolve(&["--dap", "--gdb", "1234"], "").is_err());
+        assert!(resolve(&["--script", "bot.rhai", "--gdb", "1234"], "").is_err());
+        assert!(resolve(&["--profile", "out.json", "--script", "bot.rhai"], "").is_err());
+        assert!(resolve(&["--coverage", "game.cov", "--profile", "out.json"], "").is_err());
+        assert!(resolve(&["--movie", "run.c8m"], "").is_err());
+        assert!(resolve(&["--palette", "mauve"], "").is_err());
+        assert!(resolve(&[], "palette = \"mauve\"").is_err());
+        assert!(resolve(&[], "[roms.00000000000000ff]\npalette = \"mauve\"").is_err());
+    }
+
+    #[test]
+    fn test_dap_transport() -> Result<(), String> {
+        assert_eq!(resolve(&[], "")?.dap, None);
+        assert_eq!(resolve(&["--dap"], "")?.dap, Some(Transport::Stdio));
+        assert_eq!(resolve(&["--dap", "4711"], "")?.dap, Some(Transport::Socket(4711)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wav_is_headless() -> Result<(), String> {
+        assert!(resolve(&["--wav", "out.wav"], "")?.headless);
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tone::AudioSettings;
+
+const CONFIG_DIR: &str = "chip8-rs";
+const CONFIG_FILE: &str = "config.toml";
+const MAX_RECENT: usize = 10;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub palette: Option<String>,
+    pub ips: Option<u32>,
+    pub fps: Option<u32>,
+    pub scale: Option<u32>,
+    pub capture_scale: Option<u32>,
+    pub platform: Option<String>,
+    pub quirks: Option<String>,
+    pub paused: bool,
+    pub fullscreen: bool,
+    pub turbo: Option<f64>,
+    pub integer_scaling: bool,
+    pub window: Option<WindowConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub recent: Vec<String>,
+    pub browser_dir: Option<String>,
+    pub audio: AudioSettings,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub palettes: BTreeMap<String, PaletteConfig>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub roms: BTreeMap<String, RomConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaletteConfig {
+    pub background: String,
+    pub foreground: String,
+    pub plane2: Option<String>,
+    pub blend: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RomConfig {
+    pub palette: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowConfig {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Config {
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR))
+    }
+
+    pub fn load() -> Result<Self, String> {
+        let path = match Self::dir() {
+            Some(dir) => dir.join(CONFIG_FILE),
+            None => return Ok(Self::default()),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = Self::dir().ok_or("could not find the user config directory")?;
+        let contents = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        fs::write(dir.join(CONFIG_FILE), contents).map_err(|e| e.to_string())
+    }
+
+    pub fn add_recent(&mut self, path: &str) {
+        self.recent.retain(|recent| recent != path);
+        self.recent.insert(0, path.to_string());
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    pub fn rom(&self, program: &[u8]) -> Option<&RomConfig> {
+        self.roms.get(&rom_key(program))
+    }
+
+    pub fn rom_mut(&mut self, program: &[u8]) -> &mut RomConfig {
+        self.roms.entry(rom_key(program)).or_default()
+    }
+}
+
+fn rom_key(program: &[u8]) -> String {
+    format!("{:016x}", core::rom_hash(program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tone::Waveform;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(r##"
+            palette = "amber"
+
+            [palettes.mine]
+            background = "#000000"
+            foreground = "#33FF66"
+
+            [roms.00000000000000ff]
+            palette = "lcd"
+        "##).unwrap();
+
+        assert_eq!(config.palette.as_deref(), Some("amber"));
+        assert_eq!(config.palettes["mine"].foreground, "#33FF66");
+        assert_eq!(config.roms["00000000000000ff"].palette.as_deref(), Some("lcd"));
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        let config = Config::parse("").unwrap();
+        assert!(config.palette.is_none());
+        assert!(config.roms.is_empty());
+        assert_eq!(config.audio, AudioSettings::default());
+    }
+
+    #[test]
+    fn test_parse_audio() {
+        let config = Config::parse(r#"
+            [audio]
+            waveform = "triangle"
+            volume = 0.5
+        "#).unwrap();
+
+        assert_eq!(config.audio.waveform, Waveform::Triangle);
+        assert_eq!(config.audio.volume, 0.5);
+        assert_eq!(config.audio.frequency, 440.0);
+    }
+
+    #[test]
+    fn test_add_recent() {
+        let mut config = Config::default();
+
+        for i in 0..12 {
+            config.add_recent(&format!("{}.ch8", i));
+        }
+        config.add_recent("5.ch8");
+
+        assert_eq!(config.recent.len(), MAX_RECENT);
+        assert_eq!(config.recent[0], "5.ch8");
+        assert_eq!(config.recent[1], "11.ch8");
+        assert_eq!(config.recent.iter().filter(|path| *path == "5.ch8").count(), 1);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut config = Config::default();
+        config.rom_mut(&[0x00, 0xE0]).palette = Some("octo".to_string());
+
+        let parsed = Config::parse(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(parsed.rom(&[0x00, 0xE0]), config.rom(&[0x00, 0xE0]));
+        assert!(parsed.rom(&[0x12, 0x00]).is_none());
+    }
+}
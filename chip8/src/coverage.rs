@@ -0,0 +1,69 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use core::coverage::Coverage;
+
+/// Merges `coverage` into the coverage file at `path`, creating it if needed, and writes an annotated listing and an
+/// LCOV tracefile of the merged coverage next to it, with the `.lst` and `.info` extensions
+pub fn save(path: &Path, coverage: &Coverage, program: &[u8], name: &str) -> Result<(), String> {
+    let merged = merge(path, coverage)?;
+    fs::write(path, merged.to_string()).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    for (extension, contents) in [("lst", merged.listing(program)), ("info", merged.lcov(program, name))] {
+        let path = path.with_extension(extension);
+        fs::write(&path, contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+fn merge(path: &Path, coverage: &Coverage) -> Result<Coverage, String> {
+    let mut merged: Coverage = match fs::read_to_string(path) {
+        Ok(contents) => contents.parse().map_err(|e: core::Error| format!("{}: {}", path.display(), e))?,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(coverage.clone()),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+
+    merged.merge(coverage).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{Emulator, XorShift};
+
+    // 200: LD V0, 1; SE V0, 1; JP 200; JP 200
+    const PROGRAM: [u8; 8] = [0x60, 0x01, 0x30, 0x01, 0x12, 0x00, 0x12, 0x00];
+
+    #[test]
+    fn test_save_merges_runs() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("chip8-coverage-{}", std::process::id()));
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let path = dir.join("game.cov");
+
+        for _ in 0..2 {
+            let mut emulator = Emulator::<XorShift>::with_seed(0);
+            emulator.load_program(&PROGRAM);
+
+            let mut coverage = Coverage::new(&PROGRAM);
+            coverage.cycle(&mut emulator, 3).map_err(|e| e.to_string())?;
+            save(&path, &coverage, &PROGRAM, "game.ch8")?;
+        }
+
+        let merged: Coverage = fs::read_to_string(&path).map_err(|e| e.to_string())?.parse().map_err(|e: core::Error| e.to_string())?;
+        assert_eq!([0x200, 0x202, 0x204, 0x206].map(|addr| merged.executions(addr)), [2, 2, 0, 2]);
+
+        let listing = fs::read_to_string(path.with_extension("lst")).map_err(|e| e.to_string())?;
+        assert!(listing.contains("    #####:  204  1200  JP 0x200\n"));
+
+        let lcov = fs::read_to_string(path.with_extension("info")).map_err(|e| e.to_string())?;
+        assert!(lcov.contains("LF:4\nLH:3\n"));
+
+        let other = Coverage::new(&[0x12, 0x00]);
+        assert!(save(&path, &other, &[0x12, 0x00], "other.ch8").unwrap_err().ends_with("coverage was recorded with a different ROM"));
+
+        fs::remove_dir_all(&dir).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use core::{Emulator, Instruction, RandomSource};
+use crate::symbols::SymbolMap;
+
+const THREAD_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+const TIMERS_REFERENCE: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Stdio,
+    Socket(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Halted,
+    Running,
+    /// Running until execution returns to `pc` at the same call depth or above
+    StepOver { pc: u16, depth: usize },
+    /// Running until the call depth drops below `depth`
+    StepOut { depth: usize },
+}
+
+/// A Debug Adapter Protocol server. The emulator is halted until the client has launched a ROM and finished
+/// configuring breakpoints, and whenever it stops on a breakpoint, a step or a pause request.
+pub struct DapServer {
+    messages: Receiver<Value>,
+    output: Box<dyn Write + Send>,
+    seq: u64,
+    state: State,
+    launched: bool,
+    configured: bool,
+    stop_on_entry: bool,
+    /// Whether the client resumed from the current instruction, so its breakpoint doesn't stop execution again
+    resumed: bool,
+    terminated: bool,
+    symbols: Option<SymbolMap>,
+    line_breakpoints: BTreeMap<PathBuf, Vec<u16>>,
+    instruction_breakpoints: BTreeSet<u16>,
+    events: Vec<(&'static str, Value)>,
+}
+
+impl DapServer {
+    /// Reads requests from `input` on a background thread and writes responses and events to `output`
+    pub fn new<I: Read + Send + 'static, O: Write + Send + 'static>(input: I, output: O) -> Self {
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || read_messages(input, sender));
+
+        Self {
+            messages,
+            output: Box::new(output),
+            seq: 0,
+            state: State::Halted,
+            launched: false,
+            configured: false,
+            stop_on_entry: false,
+            resumed: false,
+            terminated: false,
+            symbols: None,
+            line_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: BTreeSet::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn stdio() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+
+    /// Waits for a client to connect
+    pub fn accept(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream.try_clone()?, stream))
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.state == State::Halted
+    }
+
+    /// Whether the client has disconnected or asked to end the session
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+
+    /// Handles the pending requests without blocking, returning the path of a ROM the client asked to launch
+    pub fn poll<R: RandomSource>(&mut self, emulator: &mut Emulator<R>) -> io::Result<Option<PathBuf>> {
+        let mut launch = None;
+
+        while !self.terminated {
+            match self.messages.try_recv() {
+                Ok(request) => {
+                    if let Some(path) = self.handle(&request, emulator)? {
+                        launch = Some(path);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.terminated = true,
+            }
+        }
+
+        Ok(launch)
+    }
+
+    /// Runs up to `cycles` instructions, stopping early before an instruction with a breakpoint or at the end of a step
+    pub fn cycle<R: RandomSource>(&mut self, emulator: &mut Emulator<R>, cycles: u32) -> io::Result<()> {
+        for _ in 0..cycles {
+            if self.is_halted() {
+                break;
+            }
+
+            let resumed = std::mem::take(&mut self.resumed);
+
+            if !resumed && self.is_breakpoint(emulator.registers().pc) {
+                return self.stop("breakpoint", None);
+            }
+
+            if let Err(e) = emulator.cycle(1) {
+                return self.stop("exception", Some(e.to_string()));
+            }
+
+            let pc = emulator.registers().pc;
+            let depth = emulator.call_stack().len();
+
+            let reason = match self.state {
+                State::StepOver { pc: target, depth: start } if pc == target && depth <= start => "step",
+                State::StepOut { depth: start } if depth < start => "step",
+                _ => continue,
+            };
+
+            self.stop(reason, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Tells the client that the program has ended, unless the client ended the session itself
+    pub fn terminate(&mut self) -> io::Result<()> {
+        if self.terminated {
+            return Ok(());
+        }
+
+        self.terminated = true;
+        self.event("exited", json!({ "exitCode": 0 }))?;
+        self.event("terminated", Value::Null)
+    }
+
+    fn handle<R: RandomSource>(&mut self, request: &Value, emulator: &mut Emulator<R>) -> io::Result<Option<PathBuf>> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let mut launch = None;
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(args).map(|path| {
+                launch = Some(path);
+                Value::Null
+            }),
+            "setBreakpoints" => Ok(self.set_breakpoints(args)),
+            "setInstructionBreakpoints" => Ok(self.set_instruction_breakpoints(args)),
+            "configurationDone" => {
+                self.configured = true;
+                self.start();
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => Ok(self.stack_trace(emulator)),
+            "scopes" => Ok(json!({
+                "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                    { "name": "Timers", "variablesReference": TIMERS_REFERENCE, "expensive": false },
+                ],
+            })),
+            "variables" => variables(args, emulator),
+            "continue" => {
+                self.resume(State::Running);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                let pc = emulator.registers().pc;
+                let opcode = emulator.memory.get(pc as usize..pc as usize + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+
+                match opcode.map(Instruction::try_from) {
+                    Some(Ok(Instruction::CALL(_))) => {
+                        self.resume(State::StepOver { pc: pc + 2, depth: emulator.call_stack().len() });
+                    }
+                    _ => self.step(emulator),
+                }
+
+                Ok(Value::Null)
+            }
+            "stepIn" => {
+                self.step(emulator);
+                Ok(Value::Null)
+            }
+            "stepOut" => {
+                match emulator.call_stack().len() {
+                    0 => self.step(emulator),
+                    depth => self.resume(State::StepOut { depth }),
+                }
+
+                Ok(Value::Null)
+            }
+            "pause" => {
+                if !self.is_halted() {
+                    self.state = State::Halted;
+                    self.events.push(("stopped", stopped("pause", None)));
+                }
+
+                Ok(Value::Null)
+            }
+            "disconnect" | "terminate" => {
+                self.terminated = true;
+                Ok(Value::Null)
+            }
+            _ => Err(format!("unsupported command: {}", command)),
+        };
+
+        self.respond(request, result)?;
+
+        for (event, body) in std::mem::take(&mut self.events) {
+            self.event(event, body)?;
+        }
+
+        Ok(launch)
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<PathBuf, String> {
+        let path = PathBuf::from(args["program"].as_str().ok_or("missing program")?);
+
+        if !path.is_file() {
+            return Err(format!("{}: not a file", path.display()));
+        }
+
+        let symbols = match args["symbols"].as_str() {
+            Some(symbols) => Some(SymbolMap::load(Path::new(symbols))?),
+            None => Some(path.with_extension("sym")).filter(|path| path.is_file()).map(|path| SymbolMap::load(&path)).transpose()?,
+        };
+
+        self.symbols = symbols;
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.launched = true;
+        self.start();
+        self.events.push(("initialized", Value::Null));
+        Ok(path)
+    }
+
+    fn start(&mut self) {
+        if !self.launched || !self.configured {
+            return;
+        }
+
+        if self.stop_on_entry {
+            self.events.push(("stopped", stopped("entry", None)));
+        } else {
+            self.state = State::Running;
+        }
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        let path = PathBuf::from(args["source"]["path"].as_str().unwrap_or_default());
+        let mut addresses = Vec::new();
+        let mut breakpoints = Vec::new();
+
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"].as_u64().unwrap_or_default() as u32;
+
+            let resolved = match &self.symbols {
+                Some(symbols) => symbols.address(&path, line).ok_or("no code at this line"),
+                None => Err("no symbols loaded"),
+            };
+
+            breakpoints.push(match resolved {
+                Ok((addr, line)) => {
+                    addresses.push(addr);
+                    json!({ "verified": true, "line": line, "instructionReference": address(addr) })
+                }
+                Err(message) => json!({ "verified": false, "line": line, "message": message }),
+            });
+        }
+
+        self.line_breakpoints.insert(path, addresses);
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Value {
+        self.instruction_breakpoints.clear();
+        let mut breakpoints = Vec::new();
+
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let reference = breakpoint["instructionReference"].as_str().unwrap_or_default();
+            let offset = breakpoint["offset"].as_i64().unwrap_or_default();
+
+            let addr = parse_address(reference)
+                .map(|addr| addr as i64 + offset)
+                .filter(|addr| (0..0x1000).contains(addr))
+                .map(|addr| addr as u16);
+
+            breakpoints.push(match addr {
+                Some(addr) => {
+                    self.instruction_breakpoints.insert(addr);
+                    json!({ "verified": true, "instructionReference": address(addr) })
+                }
+                None => json!({ "verified": false, "message": "invalid address" }),
+            });
+        }
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn stack_trace<R: RandomSource>(&self, emulator: &Emulator<R>) -> Value {
+        let pc = emulator.registers().pc;
+        let calls = emulator.call_stack().iter().rev().map(|ret| ret.saturating_sub(2) as u16);
+
+        let frames: Vec<Value> = [pc].into_iter().chain(calls).enumerate().map(|(id, addr)| {
+            let symbols = self.symbols.as_ref();
+            let name = symbols.and_then(|symbols| symbols.label(addr)).map_or_else(|| address(addr), str::to_string);
+            let mut frame = json!({ "id": id, "name": name, "line": 0, "column": 0, "instructionPointerReference": address(addr) });
+
+            if let Some((path, line)) = symbols.and_then(|symbols| symbols.location(addr)) {
+                let name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+                frame["source"] = json!({ "name": name, "path": path });
+                frame["line"] = json!(line);
+                frame["column"] = json!(1);
+            }
+
+            frame
+        }).collect();
+
+        json!({ "totalFrames": frames.len(), "stackFrames": frames })
+    }
+
+    fn is_breakpoint(&self, pc: u16) -> bool {
+        self.instruction_breakpoints.contains(&pc) || self.line_breakpoints.values().any(|addresses| addresses.contains(&pc))
+    }
+
+    fn resume(&mut self, state: State) {
+        self.state = state;
+        self.resumed = true;
+    }
+
+    fn step<R: RandomSource>(&mut self, emulator: &mut Emulator<R>) {
+        self.state = State::Halted;
+
+        let body = match emulator.cycle(1) {
+            Ok(()) => stopped("step", None),
+            Err(e) => stopped("exception", Some(e.to_string())),
+        };
+
+        self.events.push(("stopped", body));
+    }
+
+    fn stop(&mut self, reason: &str, description: Option<String>) -> io::Result<()> {
+        self.state = State::Halted;
+        self.event("stopped", stopped(reason, description))
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        let mut message = json!({ "type": "event", "event": event });
+
+        if !body.is_null() {
+            message["body"] = body;
+        }
+
+        self.send(message)
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.output.flush()
+    }
+}
+
+fn variables<R: RandomSource>(args: &Value, emulator: &Emulator<R>) -> Result<Value, String> {
+    let registers = emulator.registers();
+
+    let variables: Vec<(String, String)> = match args["variablesReference"].as_u64() {
+        Some(REGISTERS_REFERENCE) => (0..16)
+            .map(|x| (format!("V{:X}", x), format!("0x{:02X}", registers.v[x])))
+            .chain([
+                ("I".to_string(), address(registers.i)),
+                ("PC".to_string(), address(registers.pc)),
+                ("SP".to_string(), registers.sp.to_string()),
+            ])
+            .collect(),
+        Some(TIMERS_REFERENCE) => vec![
+            ("DT".to_string(), registers.dt.to_string()),
+            ("ST".to_string(), registers.st.to_string()),
+        ],
+        _ => return Err("invalid variables reference".to_string()),
+    };
+
+    let variables: Vec<Value> = variables
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value, "variablesReference": 0 }))
+        .collect();
+
+    Ok(json!({ "variables": variables }))
+}
+
+fn stopped(reason: &str, description: Option<String>) -> Value {
+    let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+
+    if let Some(description) = description {
+        body["description"] = json!(description);
+    }
+
+    body
+}
+
+fn address(addr: u16) -> String {
+    format!("0x{:03X}", addr)
+}
+
+fn parse_address(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn read_messages<I: Read>(input: I, sender: Sender<Value>) {
+    let mut reader = BufReader::new(input);
+
+    while let Ok(Some(message)) = read_message(&mut reader) {
+        if sender.send(message).is_err() {
+            break;
+        }
+    }
+}
+
+/// Reads one `Content-Length` framed message, or `None` at the end of the input
+fn read_message<B: BufRead>(reader: &mut B) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        match line.trim_end() {
+            "" if length.is_some() => break,
+            header => {
+                if let Some(value) = header.strip_prefix("Content-Length:") {
+                    length = value.trim().parse().ok();
+                }
+            }
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or_default()];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+const HASHES_FILE: &str = "sha1-hashes.json";
+const PROGRAMS_FILE: &str = "programs.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Program {
+    pub title: String,
+    pub description: Option<String>,
+    pub release: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub roms: HashMap<String, Rom>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Rom {
+    #[serde(default)]
+    pub platforms: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Database {
+    hashes: HashMap<String, usize>,
+    programs: Vec<Program>,
+}
+
+impl Database {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let read = |file: &str| {
+            let path = dir.join(file);
+            fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+        };
+
+        Self::parse(&read(HASHES_FILE)?, &read(PROGRAMS_FILE)?)
+    }
+
+    pub fn parse(hashes: &str, programs: &str) -> Result<Self, String> {
+        Ok(Self {
+            hashes: serde_json::from_str(hashes).map_err(|e| e.to_string())?,
+            programs: serde_json::from_str(programs).map_err(|e| e.to_string())?,
+        })
+    }
+
+    pub fn lookup(&self, program: &[u8]) -> Option<(&Program, Option<&Rom>)> {
+        let hash = sha1_smol::Sha1::from(program).digest().to_string();
+        let program = self.programs.get(*self.hashes.get(&hash)?)?;
+        Some((program, program.roms.get(&hash)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASHES: &str = r#"{ "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3": 0 }"#;
+    const PROGRAMS: &str = r#"[
+        {
+            "title": "Test",
+            "authors": ["Someone"],
+            "release": "1990",
+            "unknown": true,
+            "roms": { "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3": { "platforms": ["originalChip8"], "tickrate": 15 } }
+        }
+    ]"#;
+
+    #[test]
+    fn test_lookup() -> Result<(), String> {
+        let database = Database::parse(HASHES, PROGRAMS)?;
+        let (program, rom) = database.lookup(b"test").unwrap();
+
+        assert_eq!(program.title, "Test");
+        assert_eq!(program.authors, ["Someone"]);
+        assert_eq!(rom.unwrap().platforms, ["originalChip8"]);
+        assert!(database.lookup(b"other").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Database::parse("[]", PROGRAMS).is_err());
+        assert!(Database::parse(HASHES, "{").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use std::ops::Range;
+
+use sdl2::pixels::Color;
+
+use core::Display;
+use crate::palette::Palette;
+
+pub const FILTER_SCALE: usize = 4;
+
+const PHOSPHOR_DECAY: f32 = 0.35;
+const LCD_RESPONSE: f32 = 0.45;
+const SCANLINE_BRIGHTNESS: f32 = 0.55;
+const GRID_BLEND: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Persistence,
+    Scanlines,
+    PixelGrid,
+    LcdResponse,
+}
+
+impl Filter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Persistence => "Persistence",
+            Self::Scanlines => "Scanlines",
+            Self::PixelGrid => "Pixel grid",
+            Self::LcdResponse => "LCD response",
+        }
+    }
+}
+
+pub struct Filters {
+    pub persistence: bool,
+    pub scanlines: bool,
+    pub pixel_grid: bool,
+    pub lcd_response: bool,
+    width: usize,
+    height: usize,
+    intensity: Vec<f32>,
+    row: Vec<u8>,
+    fading: bool,
+    redraw: bool,
+}
+
+impl Filters {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            persistence: false,
+            scanlines: false,
+            pixel_grid: false,
+            lcd_response: false,
+            width,
+            height,
+            intensity: vec![0.0; width * height],
+            row: vec![0; width * FILTER_SCALE * FILTER_SCALE * 3],
+            fading: false,
+            redraw: true,
+        }
+    }
+
+    pub fn toggle(&mut self, filter: Filter) -> bool {
+        let enabled = match filter {
+            Filter::Persistence => &mut self.persistence,
+            Filter::Scanlines => &mut self.scanlines,
+            Filter::PixelGrid => &mut self.pixel_grid,
+            Filter::LcdResponse => &mut self.lcd_response,
+        };
+
+        *enabled = !*enabled;
+        self.redraw = true;
+        *enabled
+    }
+
+    pub fn output_size(&self) -> (usize, usize) {
+        (self.width * FILTER_SCALE, self.height * FILTER_SCALE)
+    }
+
+    /// Renders the rows of `display` that changed into `out`, or every row while a fade is in progress or after a filter
+    /// was toggled, and returns the range of output rows that changed. Fades advance by `frames`, the time since the
+    /// last call in 60 Hz frames, so they run at the same speed whatever the refresh rate.
+    pub fn apply(&mut self, display: &Display, palette: &Palette, frames: f32, out: &mut [u8]) -> Option<Range<usize>> {
+        let all = self.redraw || self.fading;
+        let spans = display.dirty_spans().filter(|_| !all).chain(all.then_some(0..self.height));
+        let plain = !(self.persistence || self.scanlines || self.pixel_grid || self.lcd_response);
+        let mut changed: Option<Range<usize>> = None;
+
+        self.redraw = false;
+        self.fading = false;
+
+        for y in spans.flatten() {
+            let row_changed = if plain {
+                self.render_row(display, palette, y, out)
+            } else {
+                self.filter_row(display, palette, y, frames, out)
+            };
+
+            if row_changed {
+                changed = Some(changed.map_or(y * FILTER_SCALE, |rows| rows.start)..(y + 1) * FILTER_SCALE);
+            }
+        }
+
+        changed
+    }
+
+    /// Renders row `y` without filters, the same way as screenshots and recordings
+    fn render_row(&mut self, display: &Display, palette: &Palette, y: usize, out: &mut [u8]) -> bool {
+        display.render_rows_rgb24(&mut self.row, palette.rgb24(), FILTER_SCALE, y..y + 1);
+
+        for x in 0..self.width {
+            self.intensity[y * self.width + x] = if display.pixel(x, y) { 1.0 } else { 0.0 };
+        }
+
+        let lines = &mut out[y * self.row.len()..(y + 1) * self.row.len()];
+        let row_changed = *lines != *self.row;
+        lines.copy_from_slice(&self.row);
+        row_changed
+    }
+
+    fn filter_row(&mut self, display: &Display, palette: &Palette, y: usize, frames: f32, out: &mut [u8]) -> bool {
+        // The rates are per 60 Hz frame, a fade covers the same distance in one call of `frames` as in that many calls
+        let over = |rate: f32| 1.0 - (1.0 - rate).powf(frames);
+        let rise = if self.lcd_response { over(LCD_RESPONSE) } else { 1.0 };
+        let fall = if self.persistence { over(PHOSPHOR_DECAY) } else { rise };
+        let stride = self.width * FILTER_SCALE * 3;
+        let mut row_changed = false;
+
+        for x in 0..self.width {
+            let target = if display.pixel(x, y) { 1.0 } else { 0.0 };
+            let value = &mut self.intensity[y * self.width + x];
+
+            let rate = if target > *value { rise } else { fall };
+            *value += (target - *value) * rate;
+
+            if (target - *value).abs() < 1.0 / 255.0 {
+                *value = target;
+            } else {
+                self.fading = true;
+            }
+
+            let color = mix(palette.background, palette.foreground, *value);
+
+            for sy in 0..FILTER_SCALE {
+                for sx in 0..FILTER_SCALE {
+                    let mut pixel = color;
+
+                    if self.pixel_grid && (sx == FILTER_SCALE - 1 || sy == FILTER_SCALE - 1) {
+                        pixel = mix(pixel, palette.background, GRID_BLEND);
+                    }
+
+                    if self.scanlines && sy == FILTER_SCALE - 1 {
+                        pixel = mix(Color::BLACK, pixel, SCANLINE_BRIGHTNESS);
+                    }
+
+                    let i = (y * FILTER_SCALE + sy) * stride + (x * FILTER_SCALE + sx) * 3;
+                    let (r, g, b) = pixel.rgb();
+
+                    if out[i] != r || out[i + 1] != g || out[i + 2] != b {
+                        row_changed = true;
+                        out[i] = r;
+                        out[i + 1] = g;
+                        out[i + 2] = b;
+                    }
+                }
+            }
+        }
+
+        row_changed
+    }
+}
+
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Color::RGB(channel(from.r, to.r), channel(from.g, to.g), channel(from.b, to.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::CLASSIC;
+
+    fn output(filters: &Filters) -> Vec<u8> {
+        let (width, height) = filters.output_size();
+        vec![0; width * height * 3]
+    }
+
+    #[test]
+    fn test_no_filters() {
+        let mut filters = Filters::new(64, 32);
+        let mut out = output(&filters);
+        let mut display = Display::new();
+        display.toggle(1, 0);
+
+        assert_eq!(filters.apply(&display, &CLASSIC, 1.0, &mut out), Some(0..FILTER_SCALE));
+        assert_eq!(out[FILTER_SCALE * 3], 255);
+        assert_eq!(out[0], 0);
+        assert_eq!(filters.apply(&display, &CLASSIC, 1.0, &mut out), None);
+    }
+
+    #[test]
+    fn test_dirty_rows_only() {
+        let mut filters = Filters::new(64, 32);
+        let mut out = output(&filters);
+        let mut display = Display::new();
+        filters.apply(&display, &CLASSIC, 1.0, &mut out);
+        display.clear_dirty();
+
+        // Rows that didn't change aren't redrawn
+        let stride = 64 * FILTER_SCALE * 3;
+        out[0] = 7;
+        display.toggle(0, 5);
+        assert_eq!(filters.apply(&display, &CLASSIC, 1.0, &mut out), Some(5 * FILTER_SCALE..6 * FILTER_SCALE));
+        assert_eq!((out[0], out[5 * FILTER_SCALE * stride]), (7, 255));
+        display.clear_dirty();
+
+        filters.toggle(Filter::Scanlines);
+        assert_eq!(filters.apply(&display, &CLASSIC, 1.0, &mut out), Some(0..6 * FILTER_SCALE));
+        assert_eq!(out[0], 0);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let mut filters = Filters::new(64, 32);
+        filters.toggle(Filter::Persistence);
+
+        let mut out = output(&filters);
+        let mut display = Display::new();
+        display.toggle(0, 0);
+        filters.apply(&display, &CLASSIC, 1.0, &mut out);
+        assert_eq!(out[0], 255);
+
+        display.clear();
+        filters.apply(&display, &CLASSIC, 1.0, &mut out);
+        assert!(out[0] > 0 && out[0] < 255);
+
+        let frames = (0..60).take_while(|_| filters.apply(&display, &CLASSIC, 1.0, &mut out).is_some()).count();
+        assert!(frames < 60);
+        assert_eq!(out[0], 0);
+    }
+
+    #[test]
+    fn test_lcd_response() {
+        let mut filters = Filters::new(64, 32);
+        filters.toggle(Filter::LcdResponse);
+
+        let mut out = output(&filters);
+        let mut display = Display::new();
+        display.toggle(0, 0);
+        filters.apply(&display, &CLASSIC, 1.0, &mut out);
+
+        assert!(out[0] > 0 && out[0] < 255);
+    }
+
+    #[test]
+    fn test_fade_follows_time() {
+        let mut display = Display::new();
+        let mut fades = [Filters::new(64, 32), Filters::new(64, 32)];
+        let mut outs = [output(&fades[0]), output(&fades[1])];
+
+        for (filters, out) in fades.iter_mut().zip(&mut outs) {
+            filters.toggle(Filter::LcdResponse);
+            filters.toggle(Filter::Persistence);
+            filters.apply(&display, &CLASSIC, 1.0, out);
+        }
+
+        // Three frames at 60 Hz and at 180 Hz end up at the same brightness
+        display.toggle(0, 0);
+        fades[0].apply(&display, &CLASSIC, 3.0, &mut outs[0]);
+        for _ in 0..9 {
+            fades[1].apply(&display, &CLASSIC, 1.0 / 3.0, &mut outs[1]);
+        }
+
+        assert!((fades[0].intensity[0] - fades[1].intensity[0]).abs() < 0.001);
+        assert!(fades[0].intensity[0] > 0.5 && fades[0].intensity[0] < 1.0);
+    }
+
+    #[test]
+    fn test_scanlines() {
+        let mut filters = Filters::new(64, 32);
+        filters.toggle(Filter::Scanlines);
+
+        let mut out = output(&filters);
+        let mut display = Display::new();
+        display.toggle(0, 0);
+        filters.apply(&display, &CLASSIC, 1.0, &mut out);
+
+        let stride = 64 * FILTER_SCALE * 3;
+        assert_eq!(out[0], 255);
+        assert!(out[(FILTER_SCALE - 1) * stride] < 255);
+    }
+}
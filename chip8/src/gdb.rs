@@ -0,0 +1,433 @@
+use std::collections::BTreeSet;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::ops::Range;
+use std::thread;
+use std::time::Duration;
+
+use core::{Emulator, RandomSource, Registers};
+
+const TARGET_XML: &str = include_str!("../assets/gdb-target.xml");
+const REGISTERS_SIZE: usize = 23;
+const MEMORY_SIZE: usize = 0x1000;
+const WRITE_RETRY: Duration = Duration::from_millis(1);
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+struct Client {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    ack: bool,
+}
+
+impl Client {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, buffer: Vec::new(), ack: true })
+    }
+
+    /// Returns the complete packets received so far, or `None` once the client disconnects
+    fn receive(&mut self) -> io::Result<Option<Vec<Incoming>>> {
+        let mut chunk = [0; 1024];
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(None),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut incoming = Vec::new();
+
+        while let Some(&first) = self.buffer.first() {
+            match first {
+                0x03 => {
+                    self.buffer.remove(0);
+                    incoming.push(Incoming::Interrupt);
+                }
+                b'$' => {
+                    let end = match self.buffer.iter().position(|&b| b == b'#') {
+                        Some(end) if self.buffer.len() >= end + 3 => end,
+                        _ => break,
+                    };
+
+                    let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+                    let data = &packet[1..end];
+                    let checksum = std::str::from_utf8(&packet[end + 1..]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+                    if checksum != Some(checksum_of(data)) {
+                        self.write(b"-")?;
+                        continue;
+                    }
+
+                    if self.ack {
+                        self.write(b"+")?;
+                    }
+
+                    incoming.push(Incoming::Packet(String::from_utf8_lossy(data).into_owned()));
+                }
+                _ => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+
+        Ok(Some(incoming))
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let mut escaped = Vec::with_capacity(data.len());
+
+        for byte in data.bytes() {
+            if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+                escaped.extend([b'}', byte ^ 0x20]);
+            } else {
+                escaped.push(byte);
+            }
+        }
+
+        let packet = format!("${}#{:02x}", String::from_utf8_lossy(&escaped), checksum_of(&escaped));
+        self.write(packet.as_bytes())
+    }
+
+    fn write(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            match self.stream.write(data) {
+                Ok(n) => data = &data[n..],
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(WRITE_RETRY),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A GDB remote serial protocol server. The emulator is halted while a debugger is attached and
+/// hasn't asked it to continue, and runs freely while no debugger is attached.
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<Client>,
+    breakpoints: BTreeSet<u16>,
+    halted: bool,
+}
+
+impl GdbStub {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, client: None, breakpoints: BTreeSet::new(), halted: false })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Accepts a debugger connection and handles its pending packets without blocking
+    pub fn poll<R: RandomSource>(&mut self, emulator: &mut Emulator<R>) -> io::Result<()> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    self.client = Some(Client::new(stream)?);
+                    self.halted = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let incoming = match self.client.as_mut().map(Client::receive).transpose()?.flatten() {
+            Some(incoming) => incoming,
+            None => {
+                self.detach();
+                return Ok(());
+            }
+        };
+
+        for message in incoming {
+            match message {
+                Incoming::Interrupt if !self.halted => self.stop(&format!("S{:02x}", SIGINT))?,
+                Incoming::Interrupt => {}
+                Incoming::Packet(packet) => {
+                    if let Some(reply) = self.handle(&packet, emulator) {
+                        self.send(&reply)?;
+                    }
+                }
+            }
+
+            if self.client.is_none() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs up to `cycles` instructions, stopping early when a breakpoint is hit
+    pub fn cycle<R: RandomSource>(&mut self, emulator: &mut Emulator<R>, cycles: u32) -> Result<(), String> {
+        if self.halted {
+            return Ok(());
+        }
+
+        for _ in 0..cycles {
+            if let Err(e) = emulator.cycle(1) {
+                if self.client.is_none() {
+                    return Err(e.to_string());
+                }
+
+                return self.stop(&format!("S{:02x}", SIGILL)).map_err(|e| e.to_string());
+            }
+
+            if self.client.is_some() && self.breakpoints.contains(&emulator.registers().pc) {
+                return self.stop(&format!("T{:02x}swbreak:;", SIGTRAP)).map_err(|e| e.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle<R: RandomSource>(&mut self, packet: &str, emulator: &mut Emulator<R>) -> Option<String> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+
+        Some(match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => hex(&encode_registers(&emulator.registers())),
+            "G" => match unhex(args).and_then(|bytes| decode_registers(&bytes)) {
+                Some(registers) if set_registers(emulator, registers).is_some() => "OK".to_string(),
+                _ => error(),
+            },
+            "p" => match usize::from_str_radix(args, 16).ok().and_then(register_range) {
+                Some(range) => hex(&encode_registers(&emulator.registers())[range]),
+                None => error(),
+            },
+            "P" => self.write_register(args, emulator).unwrap_or_else(error),
+            "m" => read_memory(args, emulator).unwrap_or_else(error),
+            "M" => write_memory(args, emulator).unwrap_or_else(error),
+            "Z" | "z" => self.breakpoint(command == "Z", args).unwrap_or_default(),
+            "s" | "c" if jump(args, emulator).is_none() => error(),
+            "s" => {
+                self.halted = true;
+
+                match emulator.cycle(1) {
+                    Ok(()) => format!("S{:02x}", SIGTRAP),
+                    Err(_) => format!("S{:02x}", SIGILL),
+                }
+            }
+            "c" => {
+                self.halted = false;
+                return None;
+            }
+            "D" => {
+                let _ = self.send("OK");
+                self.detach();
+                return None;
+            }
+            "k" => {
+                self.detach();
+                return None;
+            }
+            "H" => "OK".to_string(),
+            "q" | "Q" => self.query(packet),
+            _ => String::new(),
+        })
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, length) = match parse_pair(range) {
+                Some(pair) => pair,
+                None => return error(),
+            };
+
+            let start = offset.min(TARGET_XML.len());
+            let end = match start.checked_add(length) {
+                Some(end) => end.min(TARGET_XML.len()),
+                None => return error(),
+            };
+            let prefix = if end < TARGET_XML.len() { 'm' } else { 'l' };
+            return format!("{}{}", prefix, &TARGET_XML[start..end]);
+        }
+
+        match packet.split(':').next().unwrap_or_default() {
+            "qSupported" => "PacketSize=1000;qXfer:features:read+;swbreak+;QStartNoAckMode+".to_string(),
+            "QStartNoAckMode" => {
+                if let Some(client) = &mut self.client {
+                    client.ack = false;
+                }
+
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn write_register<R: RandomSource>(&self, args: &str, emulator: &mut Emulator<R>) -> Option<String> {
+        let (index, value) = args.split_once('=')?;
+        let range = register_range(usize::from_str_radix(index, 16).ok()?)?;
+        let value = unhex(value).filter(|value| value.len() == range.len())?;
+
+        let mut bytes = encode_registers(&emulator.registers());
+        bytes[range].copy_from_slice(&value);
+        set_registers(emulator, decode_registers(&bytes)?)?;
+        Some("OK".to_string())
+    }
+
+    fn breakpoint(&mut self, insert: bool, args: &str) -> Option<String> {
+        let mut parts = args.split(',');
+
+        if parts.next()? != "0" {
+            return None;
+        }
+
+        let addr = usize::from_str_radix(parts.next()?, 16).ok().filter(|addr| *addr < MEMORY_SIZE)?;
+
+        if insert {
+            self.breakpoints.insert(addr as u16);
+        } else {
+            self.breakpoints.remove(&(addr as u16));
+        }
+
+        Some("OK".to_string())
+    }
+
+    fn stop(&mut self, reply: &str) -> io::Result<()> {
+        self.halted = true;
+        self.send(reply)
+    }
+
+    fn send(&mut self, reply: &str) -> io::Result<()> {
+        match &mut self.client {
+            Some(client) => client.send(reply),
+            None => Ok(()),
+        }
+    }
+
+    fn detach(&mut self) {
+        self.client = None;
+        self.breakpoints.clear();
+        self.halted = false;
+    }
+}
+
+fn jump<R: RandomSource>(args: &str, emulator: &mut Emulator<R>) -> Option<()> {
+    if !args.is_empty() {
+        let pc = u16::from_str_radix(args, 16).ok()?;
+        set_registers(emulator, Registers { pc, ..emulator.registers() })?;
+    }
+
+    Some(())
+}
+
+/// Rejects addresses outside memory, and a stack pointer that differs from the emulator's, which can't be set
+fn set_registers<R: RandomSource>(emulator: &mut Emulator<R>, registers: Registers) -> Option<()> {
+    let in_memory = (registers.pc as usize) < MEMORY_SIZE && (registers.i as usize) < MEMORY_SIZE;
+
+    if !in_memory || registers.sp != emulator.registers().sp {
+        return None;
+    }
+
+    emulator.set_registers(registers);
+    Some(())
+}
+
+fn read_memory<R: RandomSource>(args: &str, emulator: &Emulator<R>) -> Option<String> {
+    let (addr, length) = parse_pair(args)?;
+
+    if addr >= MEMORY_SIZE {
+        return None;
+    }
+
+    let end = addr.checked_add(length)?.min(MEMORY_SIZE);
+    Some(hex(&emulator.memory[addr..end]))
+}
+
+fn write_memory<R: RandomSource>(args: &str, emulator: &mut Emulator<R>) -> Option<String> {
+    let (range, data) = args.split_once(':')?;
+    let (addr, length) = parse_pair(range)?;
+    let data = unhex(data).filter(|data| data.len() == length && addr.checked_add(length).is_some_and(|end| end <= MEMORY_SIZE))?;
+
+    emulator.memory[addr..addr + length].copy_from_slice(&data);
+    Some("OK".to_string())
+}
+
+/// Register layout shared with the target description: V0-VF, I, PC, SP, DT, ST, with I and PC little-endian
+fn encode_registers(registers: &Registers) -> Vec<u8> {
+    let mut bytes = registers.v.to_vec();
+    bytes.extend(registers.i.to_le_bytes());
+    bytes.extend(registers.pc.to_le_bytes());
+    bytes.extend([registers.sp, registers.dt, registers.st]);
+    bytes
+}
+
+fn decode_registers(bytes: &[u8]) -> Option<Registers> {
+    if bytes.len() != REGISTERS_SIZE {
+        return None;
+    }
+
+    Some(Registers {
+        v: bytes[..16].try_into().ok()?,
+        i: u16::from_le_bytes([bytes[16], bytes[17]]),
+        pc: u16::from_le_bytes([bytes[18], bytes[19]]),
+        sp: bytes[20],
+        dt: bytes[21],
+        st: bytes[22],
+    })
+}
+
+fn register_range(index: usize) -> Option<Range<usize>> {
+    match index {
+        0..=15 => Some(index..index + 1),
+        16 => Some(16..18),
+        17 => Some(18..20),
+        18..=20 => Some(index + 2..index + 3),
+        _ => None,
+    }
+}
+
+fn parse_pair(args: &str) -> Option<(usize, usize)> {
+    let (a, b) = args.split_once(',')?;
+    Some((usize::from_str_radix(a, 16).ok()?, usize::from_str_radix(b, 16).ok()?))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
@@ -1,10 +1,32 @@
+use std::path::Path;
 use nfd::{Response, Result as NFDResult};
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
 
-use core::Display;
+use crate::filters::Filter;
 
 pub mod audio;
+pub mod browser;
+pub mod capture;
+pub mod cheats;
+pub mod cli;
+pub mod config;
+pub mod coverage;
+pub mod dap;
+pub mod database;
+pub mod filters;
+pub mod gdb;
+pub mod osd;
+pub mod palette;
+pub mod profile;
+pub mod scheduler;
+pub mod script;
+pub mod symbols;
+pub mod text;
+pub mod timeline;
+pub mod tone;
+pub mod ui;
+pub mod viewport;
+pub mod wav;
 
 pub fn prompt_file() -> NFDResult<Option<String>> {
     let result = nfd::open_file_dialog(None, None)?;
@@ -16,27 +38,32 @@ pub fn prompt_file() -> NFDResult<Option<String>> {
     })
 }
 
-pub fn update_pixel_data(display: &Display, pixel_data: &mut [u8], color: Color) -> bool {
-    let mut update = false;
+pub fn prompt_files() -> NFDResult<Vec<String>> {
+    let result = nfd::open_file_multiple_dialog(None, None)?;
 
-    for (y, &row) in display.pixel_rows().iter().enumerate() {
-        for x in 0..64 {
-            let mask = 1 << (63 - x);
-            let pixel_color = if row & mask == 0 { Color::BLACK } else { color };
-            let (r, g, b) = pixel_color.rgb();
+    Ok(match result {
+        Response::Okay(filename) => vec![filename],
+        Response::OkayMultiple(files) => files,
+        Response::Cancel => Vec::new(),
+    })
+}
 
-            let i = (y * 64 + x) * 3;
+pub fn prompt_save_file(extension: &str) -> NFDResult<Option<String>> {
+    let result = nfd::open_save_dialog(Some(extension), None)?;
 
-            if pixel_data[i] != r || pixel_data[i + 1] != g || pixel_data[i + 2] != b {
-                update = true;
-                pixel_data[i + 0] = r;
-                pixel_data[i + 1] = g;
-                pixel_data[i + 2] = b;
-            }
-        }
-    }
+    Ok(match result {
+        Response::Okay(filename) if Path::new(&filename).extension().is_some() => Some(filename),
+        Response::Okay(filename) => Some(format!("{}.{}", filename, extension)),
+        Response::OkayMultiple(files) => Some(files[0].clone()),
+        Response::Cancel => None,
+    })
+}
 
-    update
+pub fn parse_seed(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
 }
 
 pub fn keycode_to_key(keycode: Keycode) -> Option<u8> {
@@ -60,3 +87,13 @@ pub fn keycode_to_key(keycode: Keycode) -> Option<u8> {
         _ => return None,
     })
 }
+
+pub fn keycode_to_filter(keycode: Keycode) -> Option<Filter> {
+    Some(match keycode {
+        Keycode::F1 => Filter::Persistence,
+        Keycode::F2 => Filter::Scanlines,
+        Keycode::F3 => Filter::PixelGrid,
+        Keycode::F4 => Filter::LcdResponse,
+        _ => return None,
+    })
+}
@@ -1,141 +1,1019 @@
 #![windows_subsystem = "windows"]
 
-use std::time::Duration;
-use std::fs;
-use rand::Rng;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+use clap::Parser;
 use sdl2::pixels::{Color, PixelFormatEnum};
-use sdl2::event::Event;
+use sdl2::rect::Rect;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::{Keycode, Mod};
+use sdl2::video::{FullscreenType, Window};
 
-use core::Emulator;
+use core::{Display, Emulator, Error, Quirks, SaveState, XorShift};
+use core::coverage::Coverage;
+use core::movie::{Movie, Player, Recorder};
+use core::profiler::Profiler;
 use chip8::audio;
+use chip8::browser::Browser;
+use chip8::capture::{self, GifRecorder, RawRecorder, Recording};
+use chip8::cheats::{self, CheatPanel};
+use chip8::cli::{self, Args, Settings};
+use chip8::config::{Config, WindowConfig};
+use chip8::dap::{DapServer, Transport};
+use chip8::database::Database;
+use chip8::filters::Filters;
+use chip8::gdb::GdbStub;
+use chip8::osd::Osd;
+use chip8::palette::{self, Palettes};
+use chip8::profile;
+use chip8::scheduler::{self, Scheduler};
+use chip8::script::Script;
+use chip8::symbols::SymbolMap;
+use chip8::text::Surface;
+use chip8::timeline::TIMER_HZ;
+use chip8::tone::AudioSettings;
+use chip8::ui::{self, Menu};
+use chip8::viewport;
+use chip8::wav;
 
 const WINDOW_TITLE: &str = "CHIP-8 Emulator";
-const WINDOW_SIZE: u32 = 15;
-const WIDTH: u32 = 64;
-const HEIGHT: u32 = 32;
+const WIDTH: u32 = Display::WIDTH as u32;
+const HEIGHT: u32 = Display::HEIGHT as u32;
 const FPS: u32 = 60;
-const CPF: u32 = 700 / FPS;
 
-const LIGHT_GREEN: Color = Color::RGB(80, 255, 80);
-const LIGHT_YELLOW: Color = Color::RGB(255, 255, 80);
-const LIGHT_RED: Color = Color::RGB(255, 80, 80);
+const MOVIE_EXTENSION: &str = "c8m";
+const SAVE_SLOTS: usize = 4;
+const ERROR_DURATION: Duration = Duration::from_secs(5);
 
 fn main() {
-    let startup_program = include_bytes!("../startup.ch8").to_vec();
-    let args: Vec<String> = std::env::args().collect();
+    let args = Args::parse();
 
-    let initial_program = args.get(1).map_or(
-        startup_program,
-        |file| fs::read(file).expect("could not read file"));
+    // A config file that fails to load is never saved over, so a typo doesn't reset every setting
+    let (config, config_writable) = match Config::load() {
+        Ok(config) => (config, true),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            (Config::default(), false)
+        }
+    };
 
-    if let Err(e) = run(initial_program) {
-        println!("{} {}", "Error:", e);
+    if let Err(e) = args.resolve(&config).and_then(|settings| start(settings, config, config_writable)) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
     }
 }
 
-fn run(mut program: Vec<u8>) -> Result<(), String> {
+fn start(settings: Settings, config: Config, config_writable: bool) -> Result<(), String> {
+    let program = match &settings.rom {
+        Some(path) => fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None if settings.headless => return Err("no ROM given".to_string()),
+        None => Vec::new(),
+    };
+
+    let seed = settings.seed.unwrap_or_else(rand::random);
+
+    match &settings.wav {
+        Some(wav_file) => export_wav(&program, seed, &settings, &config, wav_file),
+        None if settings.headless => run_headless(&program, seed, &settings),
+        None => run(program, seed, settings, config, config_writable),
+    }
+}
+
+fn export_wav(program: &[u8], seed: u64, settings: &Settings, config: &Config, filename: &Path) -> Result<(), String> {
+    let mut emulator = create_emulator(seed, program, settings.quirks);
+    let samples = wav::render(&mut emulator, settings.frames, settings.ips, config.audio, wav::SAMPLE_RATE)
+        .map_err(|e| e.to_string())?;
+
+    let file = File::create(filename).map_err(|e| e.to_string())?;
+    wav::write_wav(BufWriter::new(file), &samples, wav::SAMPLE_RATE).map_err(|e| e.to_string())
+}
+
+fn run_headless(program: &[u8], seed: u64, settings: &Settings) -> Result<(), String> {
+    let movie = settings.movie.as_deref().map(|path| read_movie(path, program)).transpose()?;
+    let total_frames = movie.as_ref().map_or(settings.frames, |movie| movie.frames);
+    let mut player = movie.map(Player::new);
+
+    // A movie replays with the seed, quirks and speed it was recorded with
+    let seed = player.as_ref().map_or(seed, Player::seed);
+    let quirks = player.as_ref().map_or(settings.quirks, Player::quirks);
+    let ips = player.as_ref().map_or(settings.ips, Player::ips);
+
+    let mut emulator = create_emulator(seed, program, quirks);
+    let mut script = settings.script.as_deref().map(|path| Script::load(path, &mut emulator)).transpose()?;
+    let mut profiler = settings.profile.as_ref().map(|_| Profiler::default());
+    let mut coverage = settings.coverage.as_ref().map(|_| Coverage::new(program));
+    let mut scheduler = Scheduler::new(ips);
+    scheduler.set_uncapped(true);
+
+    let mut frames = 0;
+
+    'run: while frames < total_frames {
+        scheduler.update();
+
+        while let Some(cycles) = scheduler.next_frame() {
+            match (&mut player, &mut script) {
+                (Some(active), _) => active
+                    .run_frame_with(&mut emulator, |emulator, cycles| cycle(emulator, cycles, &mut profiler, &mut coverage))
+                    .map_err(|e| e.to_string())?,
+                (_, Some(active)) => {
+                    emulator.time_step();
+                    active.cycle(&mut emulator, cycles)?;
+                    active.end_frame(&mut emulator)?;
+                    save_screenshots(active, &emulator.display, palette::CLASSIC.rgb24(), settings.capture_scale);
+                }
+                _ => {
+                    emulator.time_step();
+                    cycle(&mut emulator, cycles, &mut profiler, &mut coverage).map_err(|e| e.to_string())?;
+                }
+            }
+
+            frames += 1;
+
+            if frames == total_frames || script.as_ref().is_some_and(Script::should_quit) {
+                break 'run;
+            }
+        }
+    }
+
+    if let (Some(path), Some(profiler)) = (&settings.profile, &profiler) {
+        save_profile(path, profiler, settings.rom.as_deref())?;
+    }
+
+    if let (Some(path), Some(coverage)) = (&settings.coverage, &coverage) {
+        save_coverage(path, coverage, program, settings.rom.as_deref())?;
+    }
+
+    let mut out = io::stdout().lock();
+
+    for (x, _, on) in emulator.display.pixels() {
+        write!(out, "{}", if on { '#' } else { '.' }).map_err(|e| e.to_string())?;
+
+        if x == Display::WIDTH - 1 {
+            writeln!(out).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn run(mut program: Vec<u8>, mut seed: u64, settings: Settings, mut config: Config, config_writable: bool) -> Result<(), String> {
+    let mut dap = match settings.dap {
+        Some(Transport::Stdio) => Some(DapServer::stdio()),
+        Some(Transport::Socket(port)) => {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+                .map_err(|e| format!("could not listen for dap on port {}: {}", port, e))?;
+
+            eprintln!("Waiting for a DAP client on {}", listener.local_addr().map_err(|e| e.to_string())?);
+            Some(DapServer::accept(&listener).map_err(|e| e.to_string())?)
+        }
+        None => None,
+    };
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
-    let window = video_subsystem
-        .window(WINDOW_TITLE, WIDTH * WINDOW_SIZE, HEIGHT * WINDOW_SIZE)
-        .position_centered()
-        .build().map_err(|e| e.to_string())?;
+    let geometry = config.window.filter(|_| settings.scale.is_none());
+    let (width, height) = match (settings.scale, geometry) {
+        (None, Some(geometry)) => (geometry.width, geometry.height),
+        (scale, _) => {
+            let scale = scale.unwrap_or(cli::DEFAULT_SCALE);
+            (WIDTH * scale, HEIGHT * scale)
+        }
+    };
+
+    let mut window_builder = video_subsystem.window(WINDOW_TITLE, width, height);
+    window_builder.resizable().allow_highdpi();
+
+    match geometry {
+        Some(geometry) => window_builder.position(geometry.x, geometry.y),
+        None => window_builder.position_centered(),
+    };
+
+    if settings.fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+
+    let window = window_builder.build().map_err(|e| e.to_string())?;
+    let mut canvas_builder = window.into_canvas();
 
-    let mut canvas = window.into_canvas().present_vsync().build().map_err(|e| e.to_string())?;
+    if settings.fps.is_none() {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+
+    let mut canvas = canvas_builder.build().map_err(|e| e.to_string())?;
     let mut event_pump = sdl_context.event_pump()?;
     let audio_subsystem = sdl_context.audio()?;
 
-    let audio_device = audio::create_audio_device(&audio_subsystem)?;
+    // --mute only lasts for this session, so it's kept out of the saved config
+    let mut cli_muted = settings.mute;
+    let (mut audio_device, mut sound) = audio::create_audio_device(&audio_subsystem, audio_settings(&config, cli_muted))?;
+
+    let initial_config = config.clone();
+
+    if let Some(path) = &settings.rom {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        config.add_recent(&path.to_string_lossy());
+    }
+    audio_device.resume();
 
-    canvas.set_scale(WINDOW_SIZE as f32, WINDOW_SIZE as f32)?;
+    canvas.set_draw_color(Color::BLACK);
+    canvas.clear();
     canvas.present();
 
+    let mut filters = Filters::new(WIDTH as usize, HEIGHT as usize);
+    let (filtered_width, filtered_height) = filters.output_size();
+    let mut filtered_data = vec![0; filtered_width * filtered_height * 3];
+
     let creator = canvas.texture_creator();
     let mut texture = creator
-        .create_texture_target(PixelFormatEnum::RGB24, WIDTH, HEIGHT).map_err(|e| e.to_string())?;
+        .create_texture_target(PixelFormatEnum::RGB24, filtered_width as u32, filtered_height as u32)
+        .map_err(|e| e.to_string())?;
 
-    let mut paused = false;
+    let mut paused = settings.paused;
     let mut speed = 1.0;
-    let mut color = Color::WHITE;
-    let mut pixel_data = [0; (WIDTH * HEIGHT * 3) as usize];
+    let palettes = Palettes::new(&config);
+    let mut palette = select_palette(&settings, &config, &palettes, &program);
+    let mut rendered_palette = palette;
+    let mut recording: Option<Recording> = None;
+
+    let mut recorder: Option<Recorder> = None;
+    let mut player: Option<Player> = None;
+    let mut emulator = create_emulator(seed, &program, settings.quirks);
+    let mut script = settings.script.as_deref().map(|path| Script::load(path, &mut emulator)).transpose()?;
+    let (cheats, mut cheats_writable) = load_cheats(&program);
+    let mut cheat_panel = CheatPanel::new(cheats);
+    let mut cheats_open = false;
+    let mut cheats_enabled = true;
+    let mut profiler = settings.profile.as_ref().map(|_| Profiler::default());
+    let mut coverage = settings.coverage.as_ref().map(|_| Coverage::new(&program));
+    let mut rom_path = settings.rom.clone();
 
-    let mut rng = rand::thread_rng();
-    let mut emulator = Emulator::new(|| rng.gen_range(0..=u8::MAX));
+    let mut slot = 0;
+    let mut slots: [Option<SaveState<XorShift>>; SAVE_SLOTS] = Default::default();
+    let mut scheduler = Scheduler::new(settings.ips);
+    let present_interval = settings.fps.map(|fps| Duration::from_secs(1) / fps);
+    let mut last_present = Instant::now();
+    let mut last_filtered = Instant::now();
+    let mut dirty = false;
 
-    emulator.load_program(&program);
+    let mut open_path: Option<String> = None;
+    let mut menu: Option<Menu> = None;
+    let mut browser: Option<Browser> = None;
+    let database = load_database();
+
+    if program.is_empty() && dap.is_none() {
+        browser = Some(open_browser(&config)?);
+    }
+
+    let mut osd = Osd::new();
+    let mut error: Option<(String, Instant)> = None;
+    let mut overlay_data = filtered_data.clone();
+    let mut overlay_shown = false;
+    // Rows of `filtered_data` that haven't been uploaded to the texture yet
+    let mut stale_rows = Some(0..filtered_height);
+    let turbo_speed = config.turbo.unwrap_or(scheduler::DEFAULT_TURBO);
+    let mut turbo = false;
+    let mut fast_forward = false;
+    let mut was_fast = false;
+    let mut title = WINDOW_TITLE.to_string();
+
+    let mut gdb = match settings.gdb {
+        Some(port) => {
+            let stub = GdbStub::bind(port).map_err(|e| format!("could not listen for gdb on port {}: {}", port, e))?;
+            println!("Listening for gdb on {}", stub.local_addr().map_err(|e| e.to_string())?);
+            Some(stub)
+        }
+        None => None,
+    };
 
     'main: loop {
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => break 'main,
+                Event::Window { win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed, .. } => dirty = true,
+                Event::KeyDown { keycode: Some(Keycode::F11), .. } => {
+                    let window = canvas.window_mut();
+                    let fullscreen = match window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off,
+                    };
+
+                    window.set_fullscreen(fullscreen)?;
+                    dirty = true;
+                }
+                Event::DropFile { filename, .. } => open_path = Some(filename),
+                // Ctrl shortcuts such as Ctrl+Q stay global while the browser or the recent files menu is open
+                Event::KeyDown { keymod, keycode: Some(keycode), .. } if browser.is_some() && !ctrl_held(keymod) => {
+                    let active = browser.as_mut().unwrap();
+
+                    match (keycode, chip8::keycode_to_key(keycode)) {
+                        (Keycode::Up, _) | (_, Some(0x5)) => active.select_previous(),
+                        (Keycode::Down, _) | (_, Some(0x8)) => active.select_next(),
+                        (Keycode::Return | Keycode::KpEnter, _) | (_, Some(0x6)) => {
+                            if let Some(rom) = active.selected_rom() {
+                                open_path = Some(rom.to_string_lossy().into_owned());
+                                config.browser_dir = Some(active.dir().to_string_lossy().into_owned());
+                                browser = None;
+                            } else if let Some(dir) = active.selected_dir() {
+                                match Browser::open(dir) {
+                                    Ok(opened) => *active = opened,
+                                    Err(e) => error = Some((e, Instant::now())),
+                                }
+                            }
+                        }
+                        (Keycode::Backspace, _) | (_, Some(0x4)) => {
+                            if let Some(Ok(opened)) = active.parent().map(Browser::open) {
+                                *active = opened;
+                            }
+                        }
+                        (Keycode::Escape | Keycode::B, _) if !program.is_empty() => browser = None,
+                        _ => {}
+                    }
+                }
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } if cheats_open => cheats_open = false,
+                Event::KeyDown { keycode: Some(keycode), .. } if cheats_open => {
+                    if let Some(message) = cheat_panel.handle_key(keycode, &emulator.memory) {
+                        osd.show(message);
+                    }
+
+                    if cheat_panel.take_modified() {
+                        if let Err(e) = save_cheats(&cheat_panel.cheats, cheats_writable) {
+                            error = Some((e, Instant::now()));
+                        }
+                    }
+                }
+                Event::KeyDown { keymod, keycode: Some(keycode), .. } if menu.is_some() && !ctrl_held(keymod) => {
+                    let active = menu.as_mut().unwrap();
+
+                    match keycode {
+                        Keycode::Up => active.select_previous(),
+                        Keycode::Down => active.select_next(),
+                        Keycode::Return | Keycode::KpEnter => {
+                            open_path = active.selected().map(|i| config.recent[i].clone());
+                            menu = None;
+                        }
+                        Keycode::Escape | Keycode::H => menu = None,
+                        _ => {}
+                    }
+                }
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     paused = !paused;
+                    osd.show(if paused { "Paused" } else { "Resumed" });
 
                     if paused {
-                        canvas.window_mut().set_title(&format!("Paused · {}", WINDOW_TITLE)).unwrap();
-                    } else {
-                        canvas.window_mut().set_title(WINDOW_TITLE).unwrap();
+                        sound.silence();
+                    }
+                }
+                Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. } => fast_forward = true,
+                Event::KeyUp { keycode: Some(Keycode::Tab), .. } => fast_forward = false,
+                Event::KeyDown { keycode: Some(Keycode::F6), .. } => {
+                    cheats_enabled = !cheats_enabled;
+                    osd.show(format!("Cheats {}", on_off(cheats_enabled)));
+                }
+                Event::KeyDown { keycode: Some(Keycode::F12), .. } => {
+                    let path = capture::next_filename(Path::new("."), "png");
+                    let colors = palettes.get(palette).rgb24();
+
+                    match capture::save_screenshot(&path, &emulator.display, colors, settings.capture_scale) {
+                        Ok(()) => osd.show("Screenshot saved"),
+                        Err(e) => error = Some((e, Instant::now())),
+                    }
+                }
+                Event::KeyDown { keymod, keycode: Some(Keycode::F10), .. } => {
+                    let result = match recording.take() {
+                        Some(finished) => finished.finish().map(|_| "Recording saved"),
+                        None => start_recording(keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD), settings.capture_scale)
+                            .map(|started| recording = Some(started))
+                            .map(|_| "Recording started"),
+                    };
+
+                    match result {
+                        Ok(message) => osd.show(message),
+                        Err(e) => error = Some((e, Instant::now())),
                     }
                 }
                 Event::KeyDown { keymod, keycode: Some(keycode), .. } => {
-                    if keymod.contains(Mod::LCTRLMOD) || keymod.contains(Mod::RCTRLMOD) {
+                    if ctrl_held(keymod) {
                         match keycode {
-                            Keycode::W if speed > 0.2 => speed -= 0.1,
-                            Keycode::E if speed < 4.0 => speed += 0.1,
-                            Keycode::C => color = match color {
-                                Color::WHITE => LIGHT_GREEN,
-                                LIGHT_GREEN => LIGHT_YELLOW,
-                                LIGHT_YELLOW => LIGHT_RED,
-                                _ => Color::WHITE,
-                            },
+                            Keycode::W | Keycode::E => {
+                                if keycode == Keycode::W && speed > 0.2 {
+                                    speed -= 0.1;
+                                } else if keycode == Keycode::E && speed < 4.0 {
+                                    speed += 0.1;
+                                }
+
+                                osd.show(format!("Speed {:.1}x", speed));
+                            }
+                            Keycode::C if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                                config.rom_mut(&program).palette = Some(palettes.name(palette).to_string());
+
+                                match save_config(&config, config_writable) {
+                                    Ok(()) => osd.show("Palette saved for this ROM"),
+                                    Err(e) => error = Some((e, Instant::now())),
+                                }
+                            }
+                            Keycode::C => {
+                                palette = (palette + 1) % palettes.len();
+                                osd.show(format!("Palette: {}", capitalize(palettes.name(palette))));
+                            }
                             Keycode::R => {
-                                emulator.reset();
-                                emulator.load_program(&program);
-                            }
-                            Keycode::O => {
-                                if let Ok(Some(filename)) = chip8::prompt_file() {
-                                    program = fs::read(filename).map_err(|e| e.to_string())?;
-                                    emulator.reset();
-                                    emulator.load_program(&program);
+                                // Like opening a ROM, a reset drops the quirks and speed of a replayed movie
+                                player = None;
+                                scheduler.set_ips(settings.ips);
+
+                                if recorder.is_some() {
+                                    seed = rand::random();
+                                    recorder = Some(Recorder::new(seed, &program, settings.quirks, settings.ips));
+                                }
+
+                                emulator = create_emulator(seed, &program, settings.quirks);
+                                osd.show("Reset");
+                            }
+                            Keycode::O => match chip8::prompt_files() {
+                                Ok(files) => {
+                                    for extra in files.iter().skip(1).rev() {
+                                        config.add_recent(extra);
+                                    }
+
+                                    open_path = files.into_iter().next();
+                                }
+                                Err(_) => match open_browser(&config) {
+                                    Ok(opened) => {
+                                        browser = Some(opened);
+                                        sound.silence();
+                                    }
+                                    Err(e) => error = Some((e, Instant::now())),
+                                },
+                            },
+                            Keycode::B => match open_browser(&config) {
+                                Ok(opened) => {
+                                    browser = Some(opened);
+                                    sound.silence();
+                                }
+                                Err(e) => error = Some((e, Instant::now())),
+                            },
+                            Keycode::H => {
+                                menu = Some(Menu::new("Recent files", config.recent.clone()));
+                                sound.silence();
+                            }
+                            Keycode::F => {
+                                cheats_open = true;
+                                sound.silence();
+                            }
+                            Keycode::T => {
+                                if let Some(active) = &recorder {
+                                    // A failed save keeps recording, so it can be retried
+                                    match save_movie(active.movie()) {
+                                        Ok(()) => {
+                                            recorder = None;
+                                            osd.show("Movie recording stopped");
+                                        }
+                                        Err(e) => error = Some((format!("{}, the movie is still recording", e), Instant::now())),
+                                    }
+                                } else {
+                                    seed = rand::random();
+                                    emulator = create_emulator(seed, &program, emulator.quirks());
+                                    recorder = Some(Recorder::new(seed, &program, emulator.quirks(), scheduler.ips()));
+                                    player = None;
+
+                                    // Movies don't record cheats, so they would desync on replay
+                                    if cheats_enabled && !cheat_panel.cheats.cheats.is_empty() {
+                                        osd.show("Movie recording started, cheats are off until it stops");
+                                    } else {
+                                        osd.show("Movie recording started");
+                                    }
+                                }
+                            }
+                            Keycode::P => match load_movie(&program) {
+                                Ok(Some(movie)) => {
+                                    seed = movie.seed;
+                                    emulator = create_emulator(seed, &program, movie.quirks);
+                                    scheduler.set_ips(movie.ips);
+                                    player = Some(Player::new(movie));
+                                    recorder = None;
                                     paused = false;
+                                    osd.show("Replaying movie");
                                 }
+                                Ok(None) => {}
+                                Err(e) => error = Some((e, Instant::now())),
+                            },
+                            Keycode::U => {
+                                turbo = !turbo;
+                                osd.show(format!("Turbo {}", on_off(turbo)));
                             }
+                            Keycode::I => {
+                                config.integer_scaling = !config.integer_scaling;
+                                osd.show(format!("Integer scaling {}", on_off(config.integer_scaling)));
+                            }
+                            Keycode::M => {
+                                config.audio.muted = !(config.audio.muted || cli_muted);
+                                cli_muted = false;
+                                osd.show(if config.audio.muted { "Sound muted" } else { "Sound unmuted" });
+                            }
+                            Keycode::Up | Keycode::Down => {
+                                config.audio.adjust_volume(if keycode == Keycode::Up { 0.05 } else { -0.05 });
+                                osd.show(format!("Volume {:.0}%", config.audio.volume * 100.0));
+                            }
+                            Keycode::Right | Keycode::Left => {
+                                config.audio.adjust_pitch(if keycode == Keycode::Right { 1 } else { -1 });
+                                osd.show(format!("Pitch {:.0} Hz", config.audio.frequency));
+                            }
+                            Keycode::V => {
+                                config.audio.waveform = config.audio.waveform.next();
+                                osd.show(format!("Waveform: {}", config.audio.waveform.name()));
+                            }
+                            Keycode::Num1 | Keycode::Num2 | Keycode::Num3 | Keycode::Num4 => {
+                                slot = keycode as usize - Keycode::Num1 as usize;
+                                osd.show(format!("Slot {}", slot + 1));
+                            }
+                            Keycode::S => {
+                                slots[slot] = Some(emulator.save_state());
+                                osd.show(format!("State saved to slot {}", slot + 1));
+                            }
+                            Keycode::L if recorder.is_none() && player.is_none() => match &slots[slot] {
+                                Some(state) => {
+                                    emulator.load_state(state);
+                                    osd.show(format!("State loaded from slot {}", slot + 1));
+                                }
+                                None => osd.show(format!("Slot {} is empty", slot + 1)),
+                            },
                             Keycode::Q => break 'main,
                             _ => {}
                         }
+
+                        audio_device.lock().tone.settings = audio_settings(&config, cli_muted);
+                    } else if let Some(filter) = chip8::keycode_to_filter(keycode) {
+                        let enabled = filters.toggle(filter);
+                        osd.show(format!("{} {}", filter.name(), on_off(enabled)));
                     } else if let Some(key) = chip8::keycode_to_key(keycode) {
-                        emulator.keydown(key);
+                        if player.is_none() {
+                            emulator.keydown(key);
+                        }
+
+                        if let Some(recorder) = &mut recorder {
+                            recorder.keydown(key);
+                        }
                     }
                 }
                 Event::KeyUp { keycode: Some(keycode), .. } => {
                     if let Some(key) = chip8::keycode_to_key(keycode) {
-                        emulator.keyup(key);
+                        if player.is_none() {
+                            emulator.keyup(key);
+                        }
+
+                        if let Some(recorder) = &mut recorder {
+                            recorder.keyup(key);
+                        }
                     }
                 }
                 _ => {}
             }
         }
 
-        if !paused {
-            emulator.time_step();
-            emulator.cycle((CPF as f32 * speed) as u32).map_err(|e| e.to_string())?;
+        let fast = fast_forward || turbo;
+
+        if fast && !was_fast {
+            sound.silence();
+        }
+
+        was_fast = fast;
+        scheduler.set_fast_forward(turbo.then_some(turbo_speed));
+
+        if let Some(server) = &mut dap {
+            if let Some(path) = server.poll(&mut emulator).map_err(|e| e.to_string())? {
+                open_path = Some(path.to_string_lossy().into_owned());
+            }
+
+            if server.is_terminated() {
+                break 'main;
+            }
+        }
+
+        if let Some(path) = open_path.take() {
+            match fs::read(&path) {
+                Ok(contents) => {
+                    // Coverage restarts with the new ROM, so what was recorded so far is merged into the file first
+                    let saved = match (&settings.coverage, &coverage) {
+                        (Some(path), Some(coverage)) => save_coverage(path, coverage, &program, rom_path.as_deref()),
+                        _ => Ok(()),
+                    };
+
+                    program = contents;
+                    emulator = create_emulator(seed, &program, settings.quirks);
+                    scheduler.set_ips(settings.ips);
+                    palette = select_palette(&settings, &config, &palettes, &program);
+                    let (cheats, writable) = load_cheats(&program);
+                    cheat_panel = CheatPanel::new(cheats);
+                    cheats_writable = writable;
+                    cheats_open = false;
+                    profiler = profiler.map(|_| Profiler::default());
+                    coverage = coverage.map(|_| Coverage::new(&program));
+                    rom_path = Some(PathBuf::from(&path));
+                    recorder = None;
+                    player = None;
+                    paused = false;
+                    error = saved.err().map(|e| (format!("Could not save coverage: {}", e), Instant::now()));
+                    config.add_recent(&path);
+                }
+                Err(e) => error = Some((format!("Could not open {}: {}", path, e), Instant::now())),
+            }
+        }
+
+        if error.as_ref().is_some_and(|(_, shown)| shown.elapsed() >= ERROR_DURATION) {
+            error = None;
         }
 
-        if emulator.sound_timer() > 0 && !paused {
-            audio_device.resume();
+        if let Some(stub) = &mut gdb {
+            stub.poll(&mut emulator).map_err(|e| e.to_string())?;
+        }
+
+        let halted = gdb.as_ref().is_some_and(GdbStub::is_halted) || dap.as_ref().is_some_and(DapServer::is_halted);
+
+        let running = !(paused || halted || cheats_open || menu.is_some() || browser.is_some());
+        scheduler.set_uncapped(fast_forward && running);
+
+        if running {
+            scheduler.set_speed(speed);
+            scheduler.update();
         } else {
-            audio_device.pause();
+            scheduler.resync();
         }
 
-        if chip8::update_pixel_data(&emulator.display, &mut pixel_data, color) {
-            texture.update(None, &pixel_data, WIDTH as usize * 3).unwrap();
-            canvas.copy(&texture, None, None).unwrap();
+        if palette != rendered_palette {
+            emulator.display.invalidate();
+            rendered_palette = palette;
+        }
+
+        while let Some(cycles) = scheduler.next_frame() {
+            if gdb.as_ref().is_some_and(GdbStub::is_halted) || dap.as_ref().is_some_and(DapServer::is_halted) {
+                break;
+            }
+
+            if let Some(active) = &mut player {
+                match active.run_frame_with(&mut emulator, |emulator, cycles| cycle(emulator, cycles, &mut profiler, &mut coverage)) {
+                    Ok(()) if active.finished() => {
+                        player = None;
+                        osd.show("Replay finished");
+                    }
+                    Ok(()) => {}
+                    Err(Error::Desync { frame }) => {
+                        eprintln!("Error: movie desynced at frame {}", frame);
+                        osd.show(format!("Replay desynced at frame {}", frame));
+                        player = None;
+                    }
+                    Err(e) => return Err(e.to_string()),
+                }
+            } else {
+                emulator.time_step();
+
+                if cheats_enabled && recorder.is_none() {
+                    cheat_panel.cheats.apply(&mut emulator);
+                }
+
+                match (&mut gdb, &mut dap, &mut script) {
+                    (Some(stub), _, _) => stub.cycle(&mut emulator, cycles)?,
+                    (_, Some(server), _) => server.cycle(&mut emulator, cycles).map_err(|e| e.to_string())?,
+                    (_, _, Some(active)) => {
+                        active.cycle(&mut emulator, cycles)?;
+                        active.end_frame(&mut emulator)?;
+                        save_screenshots(active, &emulator.display, palettes.get(palette).rgb24(), settings.capture_scale);
+                    }
+                    _ => cycle(&mut emulator, cycles, &mut profiler, &mut coverage).map_err(|e| e.to_string())?,
+                }
+
+                if let Some(recorder) = &mut recorder {
+                    recorder.end_frame(&emulator, cycles);
+                }
+            }
+
+            if !fast {
+                sound.end_frame(emulator.sound_timer());
+            }
+
+            // Recordings need every emulated frame, the window only the last one of each host frame
+            if let Some(active) = &mut recording {
+                if let Err(e) = active.push_frame(&emulator.display, palettes.get(palette).rgb24()) {
+                    error = Some((e, Instant::now()));
+                    recording = None;
+                }
+            }
+
+            if script.as_ref().is_some_and(Script::should_quit) {
+                break 'main;
+            }
+        }
+
+        // Fades follow the time since the last host frame, so they keep going while paused or in a menu
+        let now = Instant::now();
+        let frames = now.duration_since(last_filtered).as_secs_f32() * TIMER_HZ as f32;
+        last_filtered = now;
+
+        if let Some(rows) = filters.apply(&emulator.display, &palettes.get(palette), frames, &mut filtered_data) {
+            stale_rows = Some(merge_rows(stale_rows, rows));
+            dirty = true;
+        }
+
+        emulator.display.clear_dirty();
+
+        let hud = script.as_ref().is_some_and(Script::has_draw_hooks);
+        let overlay = osd.update(now) || hud || cheats_open || menu.is_some() || browser.is_some() || error.is_some();
+        dirty |= overlay || overlay_shown;
+        overlay_shown = overlay;
+
+        let present_due = present_interval.is_none_or(|interval| last_present.elapsed() >= interval);
+
+        if dirty && present_due {
+            last_present = Instant::now();
+            dirty = false;
+
+            if overlay {
+                overlay_data.copy_from_slice(&filtered_data);
+                let mut surface = Surface::new(&mut overlay_data, filtered_width, filtered_height);
+
+                if let Some(active) = &mut browser {
+                    active.update_info(&database, filtered_width);
+                    active.draw(&mut surface);
+                }
+
+                if cheats_open {
+                    cheat_panel.draw(&mut surface, &emulator.memory);
+                }
+
+                if let Some(active) = &menu {
+                    active.draw(&mut surface);
+                }
+
+                if let Some((message, _)) = &error {
+                    ui::draw_error(&mut surface, message);
+                }
+
+                if let Some(active) = script.as_mut().filter(|_| hud) {
+                    for text in active.draw(&mut emulator)? {
+                        surface.draw_text(text.x, text.y, &text.text, Color::WHITE);
+                    }
+                }
+
+                osd.draw(&mut surface, now);
+
+                texture.update(None, &overlay_data, filtered_width * 3).unwrap();
+
+                // The overlay covers the whole texture until it's hidden
+                stale_rows = Some(0..filtered_height);
+            } else if let Some(rows) = stale_rows.take() {
+                let pitch = filtered_width * 3;
+                let rect = Rect::new(0, rows.start as i32, filtered_width as u32, rows.len() as u32);
+                texture.update(rect, &filtered_data[rows.start * pitch..rows.end * pitch], pitch).unwrap();
+            }
+
+            let content = (emulator.display.width() as u32, emulator.display.height() as u32);
+            let target = viewport::viewport(canvas.output_size()?, content, config.integer_scaling);
+
+            canvas.clear();
+            canvas.copy(&texture, None, target)?;
             canvas.present();
         }
 
-        spin_sleep::sleep(Duration::new(0, 1_000_000_000 / FPS));
+        let achieved_speed = (fast && !paused).then(|| scheduler.achieved_speed());
+        let new_title = window_title(paused, &recorder, &player, achieved_speed);
+
+        if new_title != title {
+            canvas.window_mut().set_title(&new_title).map_err(|e| e.to_string())?;
+            title = new_title;
+        }
+
+        let mut sleep = scheduler.time_until_next_frame();
+
+        if let Some(interval) = present_interval {
+            sleep = sleep.min(interval.saturating_sub(last_present.elapsed()));
+        }
+
+        spin_sleep::sleep(sleep);
+    }
+
+    if let Some(active) = recording {
+        active.finish()?;
+    }
+
+    if let Some(server) = &mut dap {
+        let _ = server.terminate();
+    }
+
+    // Failing to save the profile or coverage is reported once the config is saved, so the config isn't lost
+    let mut errors = Vec::new();
+
+    if let (Some(path), Some(profiler)) = (&settings.profile, &profiler) {
+        errors.extend(save_profile(path, profiler, rom_path.as_deref()).err());
+    }
+
+    if let (Some(path), Some(coverage)) = (&settings.coverage, &coverage) {
+        errors.extend(save_coverage(path, coverage, &program, rom_path.as_deref()).err());
+    }
+
+    if canvas.window().fullscreen_state() == FullscreenType::Off {
+        config.window = Some(window_geometry(canvas.window()));
+    }
+
+    // The load error was already reported on startup
+    if config_writable && config != initial_config {
+        errors.extend(config.save().err());
+    }
+
+    match errors.pop() {
+        Some(last) => {
+            for e in errors {
+                eprintln!("Error: {}", e);
+            }
+
+            Err(last)
+        }
+        None => Ok(()),
+    }
+}
+
+fn save_screenshots(script: &mut Script, display: &Display, colors: [[u8; 3]; 2], scale: usize) {
+    for path in script.take_screenshots() {
+        if let Err(e) = capture::save_screenshot(&path, display, colors, scale) {
+            eprintln!("Error: {}", e);
+        }
+    }
+}
+
+fn merge_rows(rows: Option<Range<usize>>, more: Range<usize>) -> Range<usize> {
+    match rows {
+        Some(rows) => rows.start.min(more.start)..rows.end.max(more.end),
+        None => more,
+    }
+}
+
+fn audio_settings(config: &Config, cli_muted: bool) -> AudioSettings {
+    AudioSettings { muted: config.audio.muted || cli_muted, ..config.audio }
+}
+
+fn save_config(config: &Config, writable: bool) -> Result<(), String> {
+    if !writable {
+        return Err("not saving settings because the config file failed to load".to_string());
+    }
+
+    config.save()
+}
+
+/// Runs `cycles` instructions through the profiler or the coverage recorder, if either is enabled
+fn cycle(emulator: &mut Emulator<XorShift>, cycles: u32, profiler: &mut Option<Profiler>, coverage: &mut Option<Coverage>) -> core::Result<()> {
+    match (profiler, coverage) {
+        (Some(profiler), _) => profiler.cycle(emulator, cycles),
+        (_, Some(coverage)) => coverage.cycle(emulator, cycles),
+        _ => emulator.cycle(cycles),
+    }
+}
+
+fn save_coverage(path: &Path, coverage: &Coverage, program: &[u8], rom: Option<&Path>) -> Result<(), String> {
+    let name = rom.map_or_else(|| "rom.ch8".to_string(), |rom| rom.to_string_lossy().into_owned());
+    chip8::coverage::save(path, coverage, program, &name)
+}
+
+/// Saves the profile, naming subroutines with the labels of the symbol file next to the ROM, if there is one
+fn save_profile(path: &Path, profiler: &Profiler, rom: Option<&Path>) -> Result<(), String> {
+    let symbols = rom
+        .map(|rom| rom.with_extension("sym"))
+        .filter(|path| path.is_file())
+        .map(|path| SymbolMap::load(&path))
+        .transpose()?;
+
+    profile::save(path, profiler, symbols.as_ref())
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
+
+fn ctrl_held(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+fn open_browser(config: &Config) -> Result<Browser, String> {
+    let dir = config.browser_dir.as_deref().unwrap_or(".");
+    Browser::open(Path::new(dir)).or_else(|_| Browser::open(Path::new(".")))
+}
+
+fn load_database() -> Database {
+    let dir = match Config::dir().map(|dir| dir.join("database")).filter(|dir| dir.exists()) {
+        Some(dir) => dir,
+        None => return Database::default(),
+    };
+
+    Database::load(&dir).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        Database::default()
+    })
+}
+
+/// Loads the cheats for `program`, and whether they can be saved: a cheat file that fails to load is never saved over
+fn load_cheats(program: &[u8]) -> (core::cheats::Cheats, bool) {
+    match cheats::load(program) {
+        Ok(cheats) => (cheats, true),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            (core::cheats::Cheats::new(program), false)
+        }
+    }
+}
+
+fn save_cheats(cheats: &core::cheats::Cheats, writable: bool) -> Result<(), String> {
+    if !writable {
+        return Err("not saving cheats because the cheat file failed to load".to_string());
+    }
+
+    cheats::save(cheats)
+}
+
+fn window_geometry(window: &Window) -> WindowConfig {
+    let (x, y) = window.position();
+    let (width, height) = window.size();
+    WindowConfig { x, y, width, height }
+}
+
+fn create_emulator(seed: u64, program: &[u8], quirks: Quirks) -> Emulator<XorShift> {
+    let mut emulator = Emulator::with_seed(seed);
+    emulator.set_quirks(quirks);
+    emulator.reset();
+    emulator.load_program(program);
+    emulator
+}
+
+fn select_palette(settings: &Settings, config: &Config, palettes: &Palettes, program: &[u8]) -> usize {
+    settings.palette.as_deref()
+        .or(config.rom(program).and_then(|rom| rom.palette.as_deref()))
+        .or(config.palette.as_deref())
+        .and_then(|name| palettes.index_of(name))
+        .unwrap_or(0)
+}
+
+fn save_movie(movie: &Movie) -> Result<(), String> {
+    if let Some(filename) = chip8::prompt_save_file(MOVIE_EXTENSION).map_err(|e| e.to_string())? {
+        fs::write(filename, movie.to_string()).map_err(|e| e.to_string())?;
     }
 
     Ok(())
 }
+
+fn load_movie(program: &[u8]) -> Result<Option<Movie>, String> {
+    let filename = match chip8::prompt_file().map_err(|e| e.to_string())? {
+        Some(filename) => filename,
+        None => return Ok(None),
+    };
+
+    read_movie(Path::new(&filename), program).map(Some)
+}
+
+fn read_movie(path: &Path, program: &[u8]) -> Result<Movie, String> {
+    let movie: Movie = fs::read_to_string(path)
+        .map_err(|e| e.to_string())?
+        .parse()
+        .map_err(|e: Error| e.to_string())?;
+
+    if !movie.matches_program(program) {
+        return Err("movie was recorded with a different ROM".to_string());
+    }
+
+    Ok(movie)
+}
+
+fn start_recording(raw: bool, scale: usize) -> Result<Recording, String> {
+    let (width, height) = (WIDTH as usize, HEIGHT as usize);
+
+    Ok(if raw {
+        let path = capture::next_filename(Path::new("."), "rgb");
+        Recording::Raw(RawRecorder::new(&path, width, height, scale)?)
+    } else {
+        let path = capture::next_filename(Path::new("."), "gif");
+        Recording::Gif(GifRecorder::new(&path, width, height, scale, FPS)?)
+    })
+}
+
+fn window_title(paused: bool, recorder: &Option<Recorder>, player: &Option<Player>, speed: Option<f64>) -> String {
+    let mut title = String::new();
+
+    if paused {
+        title.push_str("Paused · ");
+    } else if let Some(speed) = speed {
+        title.push_str(&format!("{:.1}x · ", speed));
+    }
+
+    if recorder.is_some() {
+        title.push_str("Recording · ");
+    } else if player.is_some() {
+        title.push_str("Replaying · ");
+    }
+
+    title.push_str(WINDOW_TITLE);
+    title
+}
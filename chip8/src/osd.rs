@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use sdl2::pixels::Color;
+
+use crate::text::{self, Surface, GLYPH_HEIGHT, LINE_HEIGHT};
+
+pub const MESSAGE_DURATION: Duration = Duration::from_millis(2000);
+pub const FADE_DURATION: Duration = Duration::from_millis(500);
+
+const MAX_MESSAGES: usize = 3;
+const MARGIN: usize = 3;
+
+pub struct Osd {
+    messages: VecDeque<(String, Instant)>,
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Self { messages: VecDeque::new() }
+    }
+
+    pub fn show(&mut self, message: impl Into<String>) {
+        self.show_at(message, Instant::now());
+    }
+
+    pub fn show_at(&mut self, message: impl Into<String>, now: Instant) {
+        let message = message.into();
+
+        // Repeated actions like volume changes replace their previous message instead of stacking up
+        if let Some(last) = self.messages.back_mut().filter(|(last, _)| same_kind(last, &message)) {
+            *last = (message, now);
+            return;
+        }
+
+        self.messages.push_back((message, now));
+
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+
+    pub fn update(&mut self, now: Instant) -> bool {
+        self.messages.retain(|(_, shown)| now.saturating_duration_since(*shown) < MESSAGE_DURATION);
+        !self.messages.is_empty()
+    }
+
+    pub fn draw(&self, surface: &mut Surface, now: Instant) {
+        let max_chars = text::max_chars(surface.width() - MARGIN * 4);
+
+        for (i, (message, shown)) in self.messages.iter().enumerate() {
+            let alpha = opacity(now.saturating_duration_since(*shown));
+            let message = text::truncate(message, max_chars);
+            let y = MARGIN + i * (LINE_HEIGHT + 1);
+
+            let background = Color::RGBA(0, 0, 0, (alpha * 180.0) as u8);
+            let foreground = Color::RGBA(255, 255, 255, (alpha * 255.0) as u8);
+
+            surface.fill_rect(MARGIN, y, text::text_width(&message) + MARGIN * 2, GLYPH_HEIGHT + 4, background);
+            surface.draw_text(MARGIN * 2, y + 2, &message, foreground);
+        }
+    }
+}
+
+impl Default for Osd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn opacity(age: Duration) -> f32 {
+    let fade_start = MESSAGE_DURATION - FADE_DURATION;
+
+    if age <= fade_start {
+        1.0
+    } else {
+        1.0 - (age - fade_start).as_secs_f32() / FADE_DURATION.as_secs_f32()
+    }
+}
+
+fn same_kind(a: &str, b: &str) -> bool {
+    let kind = |message: &str| message.split([' ', ':']).next().unwrap_or_default().to_string();
+    kind(a) == kind(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiry() {
+        let start = Instant::now();
+        let mut osd = Osd::new();
+        osd.show_at("Reset", start);
+
+        assert!(osd.update(start + MESSAGE_DURATION / 2));
+        assert!(!osd.update(start + MESSAGE_DURATION));
+    }
+
+    #[test]
+    fn test_replace_same_kind() {
+        let start = Instant::now();
+        let mut osd = Osd::new();
+        osd.show_at("Volume 25%", start);
+        osd.show_at("Volume 30%", start);
+        osd.show_at("Palette: Amber", start);
+        osd.show_at("Speed 1.1x", start);
+        osd.show_at("Speed 1.2x", start);
+
+        let messages: Vec<_> = osd.messages.iter().map(|(message, _)| message.as_str()).collect();
+        assert_eq!(messages, ["Volume 30%", "Palette: Amber", "Speed 1.2x"]);
+
+        osd.show_at("Reset", start);
+        assert_eq!(osd.messages.len(), MAX_MESSAGES);
+        assert_eq!(osd.messages[0].0, "Palette: Amber");
+    }
+
+    #[test]
+    fn test_fade() {
+        assert_eq!(opacity(Duration::ZERO), 1.0);
+        assert_eq!(opacity(MESSAGE_DURATION - FADE_DURATION), 1.0);
+        assert!((opacity(MESSAGE_DURATION - FADE_DURATION / 2) - 0.5).abs() < 0.01);
+        assert!(opacity(MESSAGE_DURATION) <= 0.0);
+    }
+
+    #[test]
+    fn test_draw() {
+        let start = Instant::now();
+        let mut osd = Osd::new();
+        osd.show_at("State saved to slot 2", start);
+
+        let mut data = vec![0; 256 * 128 * 3];
+        osd.draw(&mut Surface::new(&mut data, 256, 128), start);
+        assert!(data.contains(&255));
+
+        let mut faded = vec![0; 256 * 128 * 3];
+        osd.draw(&mut Surface::new(&mut faded, 256, 128), start + MESSAGE_DURATION - FADE_DURATION / 4);
+        assert!(faded.iter().all(|x| *x < 255) && faded.iter().any(|x| *x > 0));
+    }
+}
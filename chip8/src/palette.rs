@@ -0,0 +1,212 @@
+use sdl2::pixels::Color;
+
+use crate::config::{Config, PaletteConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub background: Color,
+    pub foreground: Color,
+    pub plane2: Color,
+    pub blend: Color,
+}
+
+impl Palette {
+    pub const fn new(background: Color, foreground: Color) -> Self {
+        Self { background, foreground, plane2: foreground, blend: foreground }
+    }
+
+    pub fn rgb24(&self) -> [[u8; 3]; 2] {
+        [self.background, self.foreground].map(|color| [color.r, color.g, color.b])
+    }
+}
+
+pub const CLASSIC: Palette = Palette::new(Color::BLACK, Color::WHITE);
+
+pub const BUILTIN: [(&str, Palette); 7] = [
+    ("classic", CLASSIC),
+    ("green", Palette::new(Color::BLACK, Color::RGB(80, 255, 80))),
+    ("yellow", Palette::new(Color::BLACK, Color::RGB(255, 255, 80))),
+    ("red", Palette::new(Color::BLACK, Color::RGB(255, 80, 80))),
+    ("lcd", Palette {
+        background: Color::RGB(155, 188, 15),
+        foreground: Color::RGB(15, 56, 15),
+        plane2: Color::RGB(48, 98, 48),
+        blend: Color::RGB(139, 172, 15),
+    }),
+    ("amber", Palette {
+        background: Color::RGB(26, 16, 0),
+        foreground: Color::RGB(255, 176, 0),
+        plane2: Color::RGB(204, 112, 0),
+        blend: Color::RGB(255, 221, 128),
+    }),
+    ("octo", Palette {
+        background: Color::RGB(0x99, 0x66, 0x00),
+        foreground: Color::RGB(0xFF, 0xCC, 0x00),
+        plane2: Color::RGB(0xFF, 0x66, 0x00),
+        blend: Color::RGB(0x66, 0x22, 0x00),
+    }),
+];
+
+pub fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.trim().strip_prefix('#')?;
+
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color::RGB(channel(0)?, channel(2)?, channel(4)?))
+}
+
+impl TryFrom<&PaletteConfig> for Palette {
+    type Error = String;
+
+    fn try_from(config: &PaletteConfig) -> Result<Self, Self::Error> {
+        let color = |value: &str| parse_color(value).ok_or_else(|| format!("invalid color: {}", value));
+
+        let foreground = color(&config.foreground)?;
+        let optional = |value: &Option<String>| value.as_deref().map_or(Ok(foreground), color);
+
+        Ok(Self {
+            background: color(&config.background)?,
+            foreground,
+            plane2: optional(&config.plane2)?,
+            blend: optional(&config.blend)?,
+        })
+    }
+}
+
+pub struct Palettes {
+    entries: Vec<(String, Palette)>,
+}
+
+impl Palettes {
+    pub fn new(config: &Config) -> Self {
+        let mut entries: Vec<(String, Palette)> = BUILTIN
+            .iter()
+            .map(|(name, palette)| (name.to_string(), *palette))
+            .collect();
+
+        for (name, palette_config) in &config.palettes {
+            let palette = match Palette::try_from(palette_config) {
+                Ok(palette) => palette,
+                Err(e) => {
+                    eprintln!("Error: palette {}: {}", name, e);
+                    continue;
+                }
+            };
+
+            match entries.iter_mut().find(|(n, _)| n == name) {
+                Some(entry) => entry.1 = palette,
+                None => entries.push((name.clone(), palette)),
+            }
+        }
+
+        Self { entries }
+    }
+
+    /// Checks a palette name against the palettes `new` would build from `config`, without printing their errors
+    pub fn check_name(config: &Config, name: &str) -> Result<(), String> {
+        let mut names: Vec<&str> = BUILTIN.iter().map(|(name, _)| *name).collect();
+
+        for (custom, palette_config) in &config.palettes {
+            if Palette::try_from(palette_config).is_ok() && !names.contains(&custom.as_str()) {
+                names.push(custom);
+            }
+        }
+
+        if names.iter().any(|known| known.eq_ignore_ascii_case(name)) {
+            Ok(())
+        } else {
+            Err(format!("unknown palette: {} (available: {})", name, names.join(", ")))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(&self, index: usize) -> &str {
+        &self.entries[index].0
+    }
+
+    pub fn get(&self, index: usize) -> Palette {
+        self.entries[index].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#FFCC00"), Some(Color::RGB(255, 204, 0)));
+        assert_eq!(parse_color(" #0f380f "), Some(Color::RGB(15, 56, 15)));
+        assert_eq!(parse_color("FFCC00"), None);
+        assert_eq!(parse_color("#FFCC0"), None);
+        assert_eq!(parse_color("#GGCC00"), None);
+    }
+
+    #[test]
+    fn test_palette_config() {
+        let config = PaletteConfig {
+            background: "#000000".to_string(),
+            foreground: "#33FF66".to_string(),
+            plane2: None,
+            blend: Some("#112233".to_string()),
+        };
+
+        let palette = Palette::try_from(&config).unwrap();
+        assert_eq!(palette.plane2, Color::RGB(0x33, 0xFF, 0x66));
+        assert_eq!(palette.blend, Color::RGB(0x11, 0x22, 0x33));
+    }
+
+    #[test]
+    fn test_user_palettes() {
+        let mut config = Config::default();
+        config.palettes.insert("mine".to_string(), PaletteConfig {
+            background: "#101010".to_string(),
+            foreground: "#F0F0F0".to_string(),
+            plane2: None,
+            blend: None,
+        });
+        config.palettes.insert("amber".to_string(), PaletteConfig {
+            background: "#000000".to_string(),
+            foreground: "#FFAA00".to_string(),
+            plane2: None,
+            blend: None,
+        });
+
+        let palettes = Palettes::new(&config);
+        assert_eq!(palettes.len(), BUILTIN.len() + 1);
+        assert_eq!(palettes.index_of("Mine"), Some(BUILTIN.len()));
+
+        let amber = palettes.get(palettes.index_of("amber").unwrap());
+        assert_eq!(amber.foreground, Color::RGB(0xFF, 0xAA, 0x00));
+    }
+
+    #[test]
+    fn test_check_name() {
+        let mut config = Config::default();
+        config.palettes.insert("broken".to_string(), PaletteConfig {
+            background: "black".to_string(),
+            foreground: "#FFFFFF".to_string(),
+            plane2: None,
+            blend: None,
+        });
+
+        assert_eq!(Palettes::check_name(&config, "LCD"), Ok(()));
+
+        let error = Palettes::check_name(&config, "broken").unwrap_err();
+        assert_eq!(error, "unknown palette: broken (available: classic, green, yellow, red, lcd, amber, octo)");
+    }
+}
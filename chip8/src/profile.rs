@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use core::profiler::Profiler;
+use crate::symbols::SymbolMap;
+
+/// Writes a JSON report, or collapsed stacks for flamegraph tools when `path` doesn't end in `.json`
+pub fn save(path: &Path, profiler: &Profiler, symbols: Option<&SymbolMap>) -> Result<(), String> {
+    let contents = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => report(profiler, symbols).to_string(),
+        _ => collapsed(profiler, symbols),
+    };
+
+    fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Per-address hit counts, sorted from the hottest, per-kind counts and per-subroutine instruction counts
+pub fn report(profiler: &Profiler, symbols: Option<&SymbolMap>) -> Value {
+    let mut addresses: Vec<_> = (0..).zip(profiler.hits()).filter(|(_, hits)| **hits > 0).collect();
+    addresses.sort_by(|(a, a_hits), (b, b_hits)| b_hits.cmp(a_hits).then(a.cmp(b)));
+
+    let addresses: Vec<_> = addresses
+        .into_iter()
+        .map(|(addr, hits)| json!({ "address": address(addr), "name": name(addr, symbols), "hits": hits }))
+        .collect();
+
+    let mut functions: Vec<_> = profiler.functions().iter().collect();
+    functions.sort_by(|(a, a_function), (b, b_function)| b_function.inclusive.cmp(&a_function.inclusive).then(a.cmp(b)));
+
+    let functions: Vec<_> = functions
+        .into_iter()
+        .map(|(&addr, function)| {
+            json!({
+                "address": address(addr),
+                "name": name(addr, symbols),
+                "calls": function.calls,
+                "inclusive": function.inclusive,
+                "exclusive": function.exclusive,
+            })
+        })
+        .collect();
+
+    json!({
+        "instructions": profiler.total(),
+        "kinds": profiler.kinds(),
+        "addresses": addresses,
+        "functions": functions,
+    })
+}
+
+/// One `root;caller;callee instructions` line per call stack, as read by flamegraph.pl and inferno
+pub fn collapsed(profiler: &Profiler, symbols: Option<&SymbolMap>) -> String {
+    let mut lines: Vec<_> = profiler
+        .stacks()
+        .map(|(stack, instructions)| {
+            let names: Vec<_> = stack.iter().map(|&addr| name(addr, symbols)).collect();
+            format!("{} {}\n", names.join(";"), instructions)
+        })
+        .collect();
+
+    lines.sort();
+    lines.concat()
+}
+
+fn name(addr: u16, symbols: Option<&SymbolMap>) -> String {
+    symbols.and_then(|symbols| symbols.label(addr)).map_or_else(|| address(addr), str::to_string)
+}
+
+fn address(addr: u16) -> String {
+    format!("0x{:03x}", addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{Emulator, XorShift};
+
+    // 200: CALL 206; JP 202
+    // 206: RET
+    const PROGRAM: [u8; 8] = [0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x00, 0xEE];
+
+    fn profile() -> Result<Profiler, core::Error> {
+        let mut emulator = Emulator::<XorShift>::with_seed(0);
+        emulator.load_program(&PROGRAM);
+
+        let mut profiler = Profiler::default();
+        profiler.cycle(&mut emulator, 4)?;
+        Ok(profiler)
+    }
+
+    #[test]
+    fn test_report() -> Result<(), core::Error> {
+        let report = report(&profile()?, None);
+
+        assert_eq!(report["instructions"], 4);
+        assert_eq!(report["kinds"], json!({ "CALL": 1, "JP": 2, "RET": 1 }));
+        assert_eq!(report["addresses"][0], json!({ "address": "0x202", "name": "0x202", "hits": 2 }));
+        assert_eq!(report["functions"][1], json!({ "address": "0x206", "name": "0x206", "calls": 1, "inclusive": 1, "exclusive": 1 }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapsed() -> Result<(), String> {
+        let symbols = SymbolMap::parse("200 main.8o:1 main\n206 main.8o:5 tick\n", Path::new(""))?;
+        let profiler = profile().map_err(|e| e.to_string())?;
+
+        assert_eq!(collapsed(&profiler, None), "0x200 3\n0x200;0x206 1\n");
+        assert_eq!(collapsed(&profiler, Some(&symbols)), "main 3\nmain;tick 1\n");
+        Ok(())
+    }
+}
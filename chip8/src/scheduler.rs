@@ -0,0 +1,352 @@
+use std::time::{Duration, Instant};
+
+use crate::timeline::TIMER_HZ;
+
+pub const DEFAULT_IPS: u32 = 700;
+pub const DEFAULT_TURBO: f64 = 3.0;
+
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+const UNCAPPED_BUDGET: Duration = Duration::from_millis(16);
+const STATS_INTERVAL: Duration = Duration::from_millis(500);
+
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+pub struct Scheduler<C: Clock = SystemClock> {
+    clock: C,
+    ips: u32,
+    speed: f64,
+    fast_forward: Option<f64>,
+    uncapped: bool,
+    max_catch_up: Duration,
+    last_update: Duration,
+    accumulator: Duration,
+    // Set by `resync` while the emulator isn't running, until the next `update`
+    suspended: bool,
+    cycle_fraction: f64,
+    stats_start: Duration,
+    stats_frames: u32,
+    achieved_speed: f64,
+}
+
+impl Scheduler<SystemClock> {
+    pub fn new(ips: u32) -> Self {
+        Self::with_clock(SystemClock::new(), ips)
+    }
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn with_clock(clock: C, ips: u32) -> Self {
+        let last_update = clock.now();
+
+        Self {
+            clock,
+            ips,
+            speed: 1.0,
+            fast_forward: None,
+            uncapped: false,
+            max_catch_up: MAX_CATCH_UP,
+            last_update,
+            accumulator: Duration::ZERO,
+            suspended: false,
+            cycle_fraction: 0.0,
+            stats_start: last_update,
+            stats_frames: 0,
+            achieved_speed: 1.0,
+        }
+    }
+
+    pub fn frame_duration() -> Duration {
+        Duration::from_secs(1) / TIMER_HZ as u32
+    }
+
+    pub fn ips(&self) -> u32 {
+        self.ips
+    }
+
+    pub fn set_ips(&mut self, ips: u32) {
+        self.ips = ips;
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    pub fn fast_forward(&self) -> Option<f64> {
+        self.fast_forward
+    }
+
+    pub fn set_fast_forward(&mut self, multiplier: Option<f64>) {
+        self.fast_forward = multiplier;
+    }
+
+    pub fn uncapped(&self) -> bool {
+        self.uncapped
+    }
+
+    pub fn set_uncapped(&mut self, uncapped: bool) {
+        self.uncapped = uncapped;
+    }
+
+    pub fn achieved_speed(&self) -> f64 {
+        self.achieved_speed
+    }
+
+    pub fn set_max_catch_up(&mut self, max_catch_up: Duration) {
+        self.max_catch_up = max_catch_up;
+    }
+
+    pub fn update(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_update);
+        self.last_update = now;
+        self.suspended = false;
+
+        let stats_elapsed = now.saturating_sub(self.stats_start);
+        if stats_elapsed >= STATS_INTERVAL {
+            let expected = stats_elapsed.as_secs_f64() * TIMER_HZ as f64;
+            self.achieved_speed = self.stats_frames as f64 / expected;
+            self.stats_start = now;
+            self.stats_frames = 0;
+        }
+
+        if self.uncapped {
+            self.accumulator = Duration::ZERO;
+            return;
+        }
+
+        let multiplier = self.fast_forward.unwrap_or(1.0);
+        let max_catch_up = self.max_catch_up.mul_f64(multiplier);
+        self.accumulator = (self.accumulator + elapsed.mul_f64(multiplier)).min(max_catch_up);
+    }
+
+    pub fn resync(&mut self) {
+        self.last_update = self.clock.now();
+        self.accumulator = Duration::ZERO;
+        self.suspended = true;
+        self.stats_start = self.last_update;
+        self.stats_frames = 0;
+    }
+
+    pub fn next_frame(&mut self) -> Option<u32> {
+        if self.suspended {
+            return None;
+        }
+
+        let frame = Self::frame_duration();
+
+        if self.uncapped {
+            // Keep going until the time for a single display frame is used up
+            if self.clock.now().saturating_sub(self.last_update) >= UNCAPPED_BUDGET {
+                return None;
+            }
+        } else if self.accumulator < frame {
+            return None;
+        } else {
+            self.accumulator -= frame;
+        }
+
+        self.stats_frames += 1;
+        self.cycle_fraction += self.ips as f64 * self.speed / TIMER_HZ as f64;
+
+        let cycles = self.cycle_fraction.floor();
+        self.cycle_fraction -= cycles;
+        Some(cycles as u32)
+    }
+
+    pub fn time_until_next_frame(&self) -> Duration {
+        if self.uncapped {
+            return Duration::ZERO;
+        }
+
+        let remaining = Self::frame_duration().saturating_sub(self.accumulator);
+        remaining.div_f64(self.fast_forward.unwrap_or(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct MockClock {
+        now: Rc<Cell<Duration>>,
+    }
+
+    impl MockClock {
+        fn advance(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Duration {
+            self.now.get()
+        }
+    }
+
+    fn drain<C: Clock>(scheduler: &mut Scheduler<C>) -> Vec<u32> {
+        scheduler.update();
+        std::iter::from_fn(|| scheduler.next_frame()).collect()
+    }
+
+    #[test]
+    fn test_timer_rate() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), DEFAULT_IPS);
+        let mut frames = 0;
+
+        // A 144 Hz display loop
+        for _ in 0..144 {
+            clock.advance(Duration::from_secs(1) / 144);
+            frames += drain(&mut scheduler).len();
+        }
+
+        assert!((59..=60).contains(&frames));
+    }
+
+    #[test]
+    fn test_cycle_rate() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), 700);
+        let mut cycles = 0;
+
+        for _ in 0..60 {
+            clock.advance(Scheduler::<MockClock>::frame_duration());
+            cycles += drain(&mut scheduler).iter().sum::<u32>();
+        }
+
+        // 700 / 60 is not an integer, the fraction is carried over between frames
+        assert!((699..=700).contains(&cycles));
+    }
+
+    #[test]
+    fn test_speed() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), 600);
+        scheduler.set_speed(1.5);
+
+        clock.advance(Scheduler::<MockClock>::frame_duration() * 2);
+        assert_eq!(drain(&mut scheduler), [15, 15]);
+    }
+
+    #[test]
+    fn test_catch_up_limit() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), DEFAULT_IPS);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(drain(&mut scheduler).len(), 15);
+        assert!(drain(&mut scheduler).is_empty());
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), 600);
+        scheduler.set_fast_forward(Some(4.0));
+
+        clock.advance(Scheduler::<MockClock>::frame_duration() * 10);
+        let frames = drain(&mut scheduler);
+
+        assert_eq!(frames.len(), 40);
+        assert!(frames.iter().all(|c| *c == 10));
+    }
+
+    #[test]
+    fn test_uncapped() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), DEFAULT_IPS);
+        scheduler.set_uncapped(true);
+        scheduler.update();
+
+        // Every emulated frame takes 1ms, so 16 fit in a display frame
+        let mut frames = 0;
+        while scheduler.next_frame().is_some() {
+            frames += 1;
+            clock.advance(Duration::from_millis(1));
+        }
+
+        assert_eq!(frames, 16);
+        assert_eq!(scheduler.time_until_next_frame(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_achieved_speed() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), DEFAULT_IPS);
+        scheduler.set_fast_forward(Some(2.0));
+
+        for _ in 0..60 {
+            clock.advance(Scheduler::<MockClock>::frame_duration());
+            drain(&mut scheduler);
+        }
+
+        assert!((scheduler.achieved_speed() - 2.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_resync() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), DEFAULT_IPS);
+
+        clock.advance(Duration::from_millis(100));
+        scheduler.resync();
+        assert!(drain(&mut scheduler).is_empty());
+    }
+
+    #[test]
+    fn test_resync_uncapped() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), DEFAULT_IPS);
+        scheduler.set_uncapped(true);
+
+        // Paused with fast-forward held, no frames run until the scheduler is updated again
+        scheduler.resync();
+        assert_eq!(scheduler.next_frame(), None);
+
+        scheduler.update();
+        assert!(scheduler.next_frame().is_some());
+    }
+
+    #[test]
+    fn test_time_until_next_frame() {
+        let clock = MockClock::default();
+        let mut scheduler = Scheduler::with_clock(clock.clone(), DEFAULT_IPS);
+
+        clock.advance(Duration::from_millis(10));
+        scheduler.update();
+
+        let remaining = scheduler.time_until_next_frame();
+        assert!(remaining > Duration::from_millis(6) && remaining < Duration::from_millis(7));
+    }
+}
@@ -0,0 +1,366 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, AST, INT};
+
+use core::{Display, Emulator, RandomSource, Registers};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Text {
+    pub x: usize,
+    pub y: usize,
+    pub text: String,
+}
+
+#[derive(Default)]
+struct Hooks {
+    frame: Vec<FnPtr>,
+    draw: Vec<FnPtr>,
+    addresses: BTreeMap<u16, Vec<FnPtr>>,
+}
+
+/// Emulator state seen by the script functions. It's copied from the emulator before the script runs and the
+/// changes are written back afterwards, since the engine's functions can't borrow the emulator.
+struct Context {
+    memory: [u8; 0x1000],
+    registers: Registers,
+    display: Display,
+    memory_changed: bool,
+    registers_changed: bool,
+    keys: Vec<(u8, bool)>,
+    frame: INT,
+    texts: Vec<Text>,
+    screenshots: Vec<PathBuf>,
+    quit: bool,
+}
+
+impl Context {
+    fn load<R: RandomSource>(&mut self, emulator: &Emulator<R>) {
+        self.memory = emulator.memory;
+        self.registers = emulator.registers();
+        self.display.copy_from(&emulator.display);
+    }
+
+    fn store<R: RandomSource>(&mut self, emulator: &mut Emulator<R>) {
+        if mem::take(&mut self.memory_changed) {
+            emulator.memory = self.memory;
+        }
+
+        if mem::take(&mut self.registers_changed) {
+            emulator.set_registers(self.registers);
+        }
+
+        for (key, pressed) in self.keys.drain(..) {
+            if pressed {
+                emulator.keydown(key);
+            } else {
+                emulator.keyup(key);
+            }
+        }
+    }
+}
+
+/// A Rhai script driving the emulator through frame, instruction and draw hooks
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    context: Rc<RefCell<Context>>,
+    hooks: Rc<RefCell<Hooks>>,
+}
+
+impl Script {
+    pub fn load<R: RandomSource>(path: &Path, emulator: &mut Emulator<R>) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::compile(&source, emulator).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Compiles the script and runs its top level, which registers the hooks
+    pub fn compile<R: RandomSource>(source: &str, emulator: &mut Emulator<R>) -> Result<Self, String> {
+        let context = Rc::new(RefCell::new(Context {
+            memory: [0; 0x1000],
+            registers: Registers::default(),
+            display: Display::new(),
+            memory_changed: false,
+            registers_changed: false,
+            keys: Vec::new(),
+            frame: 0,
+            texts: Vec::new(),
+            screenshots: Vec::new(),
+            quit: false,
+        }));
+
+        let hooks = Rc::new(RefCell::new(Hooks::default()));
+        let engine = create_engine(&context, &hooks);
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+
+        context.borrow_mut().load(emulator);
+        engine.run_ast(&ast).map_err(|e| e.to_string())?;
+        context.borrow_mut().store(emulator);
+
+        Ok(Self { engine, ast, context, hooks })
+    }
+
+    /// Runs up to `cycles` instructions, calling the instruction hooks before the instructions at their addresses
+    pub fn cycle<R: RandomSource>(&mut self, emulator: &mut Emulator<R>, cycles: u32) -> Result<(), String> {
+        if self.hooks.borrow().addresses.is_empty() {
+            return emulator.cycle(cycles).map_err(|e| e.to_string());
+        }
+
+        for _ in 0..cycles {
+            let hooks = self.hooks.borrow().addresses.get(&emulator.registers().pc).cloned().unwrap_or_default();
+            self.call(&hooks, emulator)?;
+            emulator.cycle(1).map_err(|e| e.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Calls the frame hooks at the end of an emulated frame
+    pub fn end_frame<R: RandomSource>(&mut self, emulator: &mut Emulator<R>) -> Result<(), String> {
+        self.context.borrow_mut().frame += 1;
+        let hooks = self.hooks.borrow().frame.clone();
+        self.call(&hooks, emulator)
+    }
+
+    /// Calls the draw hooks and returns the overlay text they drew
+    pub fn draw<R: RandomSource>(&mut self, emulator: &mut Emulator<R>) -> Result<Vec<Text>, String> {
+        let hooks = self.hooks.borrow().draw.clone();
+        self.context.borrow_mut().texts.clear();
+        self.call(&hooks, emulator)?;
+        Ok(mem::take(&mut self.context.borrow_mut().texts))
+    }
+
+    pub fn has_draw_hooks(&self) -> bool {
+        !self.hooks.borrow().draw.is_empty()
+    }
+
+    pub fn take_screenshots(&mut self) -> Vec<PathBuf> {
+        mem::take(&mut self.context.borrow_mut().screenshots)
+    }
+
+    /// Whether the script asked to quit the emulator
+    pub fn should_quit(&self) -> bool {
+        self.context.borrow().quit
+    }
+
+    fn call<R: RandomSource>(&mut self, hooks: &[FnPtr], emulator: &mut Emulator<R>) -> Result<(), String> {
+        if hooks.is_empty() {
+            return Ok(());
+        }
+
+        self.context.borrow_mut().load(emulator);
+
+        for hook in hooks {
+            let _ = hook.call::<Dynamic>(&self.engine, &self.ast, ()).map_err(|e| e.to_string())?;
+        }
+
+        self.context.borrow_mut().store(emulator);
+        Ok(())
+    }
+}
+
+fn create_engine(context: &Rc<RefCell<Context>>, hooks: &Rc<RefCell<Hooks>>) -> Engine {
+    let mut engine = Engine::new();
+
+    let ctx = context.clone();
+    engine.register_fn("peek", move |addr: INT| -> ScriptResult<INT> {
+        Ok(ctx.borrow().memory[address(addr)?] as INT)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("poke", move |addr: INT, value: INT| -> ScriptResult<()> {
+        let mut ctx = ctx.borrow_mut();
+        ctx.memory[address(addr)?] = byte(value)?;
+        ctx.memory_changed = true;
+        Ok(())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("reg", move |name: &str| -> ScriptResult<INT> {
+        let registers = ctx.borrow().registers;
+
+        Ok(match name {
+            "i" => registers.i as INT,
+            "pc" => registers.pc as INT,
+            "sp" => registers.sp as INT,
+            "dt" => registers.dt as INT,
+            "st" => registers.st as INT,
+            _ => registers.v[v_index(name)?] as INT,
+        })
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("set_reg", move |name: &str, value: INT| -> ScriptResult<()> {
+        let mut ctx = ctx.borrow_mut();
+        let registers = &mut ctx.registers;
+
+        match name {
+            "i" => registers.i = address(value)? as u16,
+            "pc" => registers.pc = address(value)? as u16,
+            "dt" => registers.dt = byte(value)?,
+            "st" => registers.st = byte(value)?,
+            "sp" => return Err("sp is read-only".into()),
+            _ => registers.v[v_index(name)?] = byte(value)?,
+        }
+
+        ctx.registers_changed = true;
+        Ok(())
+    });
+
+    for (name, pressed) in [("press", true), ("release", false)] {
+        let ctx = context.clone();
+        engine.register_fn(name, move |key: INT| -> ScriptResult<()> {
+            let key = u8::try_from(key).ok().filter(|key| *key < 16).ok_or_else(|| format!("invalid key: {}", key))?;
+            ctx.borrow_mut().keys.push((key, pressed));
+            Ok(())
+        });
+    }
+
+    let ctx = context.clone();
+    engine.register_fn("pixel", move |x: INT, y: INT| -> bool {
+        let ctx = ctx.borrow();
+        x >= 0 && y >= 0 && (x as usize) < ctx.display.width() && (y as usize) < ctx.display.height() && ctx.display.pixel(x as usize, y as usize)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("frame", move || ctx.borrow().frame);
+
+    let ctx = context.clone();
+    engine.register_fn("draw_text", move |x: INT, y: INT, text: &str| {
+        let text = Text { x: x.max(0) as usize, y: y.max(0) as usize, text: text.to_string() };
+        ctx.borrow_mut().texts.push(text);
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("screenshot", move |path: &str| ctx.borrow_mut().screenshots.push(PathBuf::from(path)));
+
+    let ctx = context.clone();
+    engine.register_fn("quit", move || ctx.borrow_mut().quit = true);
+
+    let all = hooks.clone();
+    engine.register_fn("on_frame", move |hook: FnPtr| all.borrow_mut().frame.push(hook));
+
+    let all = hooks.clone();
+    engine.register_fn("on_draw", move |hook: FnPtr| all.borrow_mut().draw.push(hook));
+
+    let all = hooks.clone();
+    engine.register_fn("on_address", move |addr: INT, hook: FnPtr| -> ScriptResult<()> {
+        all.borrow_mut().addresses.entry(address(addr)? as u16).or_default().push(hook);
+        Ok(())
+    });
+
+    engine
+}
+
+fn address(value: INT) -> ScriptResult<usize> {
+    usize::try_from(value).ok().filter(|addr| *addr < 0x1000).ok_or_else(|| format!("invalid address: {}", value).into())
+}
+
+fn byte(value: INT) -> ScriptResult<u8> {
+    u8::try_from(value).map_err(|_| format!("invalid byte: {}", value).into())
+}
+
+fn v_index(name: &str) -> ScriptResult<usize> {
+    name.strip_prefix('v')
+        .filter(|index| index.len() == 1)
+        .and_then(|index| usize::from_str_radix(index, 16).ok())
+        .ok_or_else(|| format!("unknown register: {}", name).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 200: LD V0, 5
+    // 202: ADD V0, 1
+    // 204: JP 202
+    const PROGRAM: [u8; 6] = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+
+    fn emulator() -> Emulator<core::XorShift> {
+        let mut emulator = Emulator::with_seed(0);
+        emulator.reset();
+        emulator.load_program(&PROGRAM);
+        emulator
+    }
+
+    #[test]
+    fn test_memory_and_registers() -> Result<(), String> {
+        let mut emulator = emulator();
+        Script::compile("poke(0x300, peek(0x200) + 1); set_reg(\"va\", 42); set_reg(\"i\", 0x300); press(7);", &mut emulator)?;
+
+        let registers = emulator.registers();
+        assert_eq!(emulator.memory[0x300], 0x61);
+        assert_eq!((registers.v[0xA], registers.i), (42, 0x300));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let error = |source| Script::compile(source, &mut emulator()).map(drop).unwrap_err();
+        assert!(error("peek(0x1000)").contains("invalid address: 4096"));
+        assert!(error("poke(0x300, 256)").contains("invalid byte"));
+        assert!(error("reg(\"vg\")").contains("unknown register: vg"));
+        assert!(error("press(16)").contains("invalid key"));
+        assert!(!error("let x = ;").is_empty());
+    }
+
+    #[test]
+    fn test_registers_at_end_of_memory() -> Result<(), String> {
+        // DRW V0, V1, 5 split across 0xFFF and 0x000, drawing from I = 0xFFF
+        let mut emulator = emulator();
+        let mut script = Script::compile("poke(0xFFF, 0xD0); poke(0, 0x15); set_reg(\"pc\", 0xFFF); set_reg(\"i\", 0xFFF);", &mut emulator)?;
+
+        script.cycle(&mut emulator, 1)?;
+        assert_eq!(emulator.registers().pc, 0x001);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_hooks() -> Result<(), String> {
+        let mut emulator = emulator();
+        let mut script = Script::compile("on_frame(|| { if frame() == 2 { screenshot(\"shot.png\"); quit(); } });", &mut emulator)?;
+
+        script.end_frame(&mut emulator)?;
+        assert!(!script.should_quit() && script.take_screenshots().is_empty());
+
+        script.end_frame(&mut emulator)?;
+        assert!(script.should_quit());
+        assert_eq!(script.take_screenshots(), [PathBuf::from("shot.png")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_address_hooks() -> Result<(), String> {
+        let mut emulator = emulator();
+        let mut script = Script::compile("on_address(0x204, || set_reg(\"v1\", reg(\"v1\") + reg(\"v0\")));", &mut emulator)?;
+
+        script.cycle(&mut emulator, 5)?;
+        assert_eq!(emulator.registers().v[..2], [7, 13]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_draw_hooks() -> Result<(), String> {
+        let mut emulator = emulator();
+        let mut script = Script::compile("on_draw(|| draw_text(2, 3, `V0=${reg(\"v0\")} ${pixel(0, 0)}`));", &mut emulator)?;
+        assert!(script.has_draw_hooks());
+
+        emulator.cycle(1).map_err(|e| e.to_string())?;
+        emulator.display.toggle(0, 0);
+        assert_eq!(script.draw(&mut emulator)?, [Text { x: 2, y: 3, text: "V0=5 true".to_string() }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hook_errors() -> Result<(), String> {
+        let mut emulator = emulator();
+        let mut script = Script::compile("on_frame(|| poke(-1, 0));", &mut emulator)?;
+        assert!(script.end_frame(&mut emulator).unwrap_err().contains("invalid address: -1"));
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub addr: u16,
+    pub file: String,
+    pub line: u32,
+    pub label: Option<String>,
+}
+
+/// Maps program addresses to source lines. Each line of a symbol file holds a hex address, a `file:line` location
+/// relative to the symbol file and an optional label, and `#` starts a comment.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolMap {
+    dir: PathBuf,
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolMap {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, dir).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str, dir: &Path) -> Result<Self, String> {
+        let mut symbols = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+
+            let addr = match fields.next() {
+                Some(addr) => addr,
+                None => continue,
+            };
+
+            let invalid = || format!("line {}: invalid symbol", number + 1);
+            let addr = u16::from_str_radix(addr.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+            let (file, source_line) = fields.next().and_then(|location| location.rsplit_once(':')).ok_or_else(invalid)?;
+            let source_line = source_line.parse().map_err(|_| invalid())?;
+            let label = fields.next().map(str::to_string);
+
+            if fields.next().is_some() {
+                return Err(invalid());
+            }
+
+            symbols.push(Symbol { addr, file: file.to_string(), line: source_line, label });
+        }
+
+        symbols.sort_by_key(|symbol| symbol.addr);
+        Ok(Self { dir: dir.to_path_buf(), symbols })
+    }
+
+    /// Source location of the instruction at `addr`, from the closest symbol at or before it
+    pub fn location(&self, addr: u16) -> Option<(PathBuf, u32)> {
+        let symbol = self.symbols.iter().rev().find(|symbol| symbol.addr <= addr)?;
+        Some((self.dir.join(&symbol.file), symbol.line))
+    }
+
+    /// Closest label at or before `addr`
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.symbols.iter().rev().filter(|symbol| symbol.addr <= addr).find_map(|symbol| symbol.label.as_deref())
+    }
+
+    /// First address of the first line at or after `line` in `path` that has code, along with that line
+    pub fn address(&self, path: &Path, line: u32) -> Option<(u16, u32)> {
+        self.symbols
+            .iter()
+            .filter(|symbol| symbol.line >= line && self.matches(symbol, path))
+            .min_by_key(|symbol| (symbol.line, symbol.addr))
+            .map(|symbol| (symbol.addr, symbol.line))
+    }
+
+    fn matches(&self, symbol: &Symbol, path: &Path) -> bool {
+        path == self.dir.join(&symbol.file) || path.ends_with(&symbol.file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOLS: &str = "# game.8o\n200 game.8o:3 main\n202 game.8o:4\n0x208 game.8o:9 draw # sprite\n20a game.8o:10\n";
+
+    #[test]
+    fn test_location() -> Result<(), String> {
+        let symbols = SymbolMap::parse(SYMBOLS, Path::new("roms"))?;
+        assert_eq!(symbols.location(0x202), Some((PathBuf::from("roms/game.8o"), 4)));
+        assert_eq!(symbols.location(0x206), Some((PathBuf::from("roms/game.8o"), 4)));
+        assert_eq!(symbols.location(0x100), None);
+
+        assert_eq!(symbols.label(0x206), Some("main"));
+        assert_eq!(symbols.label(0x20a), Some("draw"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_address() -> Result<(), String> {
+        let symbols = SymbolMap::parse(SYMBOLS, Path::new("roms"))?;
+        assert_eq!(symbols.address(Path::new("roms/game.8o"), 4), Some((0x202, 4)));
+        assert_eq!(symbols.address(Path::new("/home/user/game/game.8o"), 5), Some((0x208, 9)));
+        assert_eq!(symbols.address(Path::new("roms/game.8o"), 11), None);
+        assert_eq!(symbols.address(Path::new("roms/other.8o"), 3), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(SymbolMap::parse("200\n", Path::new("")).unwrap_err(), "line 1: invalid symbol");
+        assert!(SymbolMap::parse("\nxyz game.8o:1\n", Path::new("")).unwrap_err().starts_with("line 2"));
+        assert!(SymbolMap::parse("200 game.8o:x\n", Path::new("")).is_err());
+        assert!(SymbolMap::parse("200 game.8o:1 main extra\n", Path::new("")).is_err());
+    }
+}
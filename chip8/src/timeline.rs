@@ -0,0 +1,239 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::tone::Tone;
+
+pub const TIMER_HZ: u64 = 60;
+
+const MAX_LATENCY_FRAMES: u64 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoundEvent {
+    pub frame: u64,
+    pub on: bool,
+}
+
+pub fn channel(sample_rate: u32) -> (SoundClock, SoundTimeline) {
+    let (sender, receiver) = mpsc::channel();
+    (SoundClock::new(sender), SoundTimeline::new(receiver, sample_rate))
+}
+
+pub struct SoundClock {
+    sender: Sender<SoundEvent>,
+    frame: u64,
+    on: bool,
+}
+
+impl SoundClock {
+    fn new(sender: Sender<SoundEvent>) -> Self {
+        Self { sender, frame: 0, on: false }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn end_frame(&mut self, sound_timer: u8) {
+        self.set(sound_timer > 0);
+        self.frame += 1;
+    }
+
+    pub fn silence(&mut self) {
+        self.set(false);
+    }
+
+    fn set(&mut self, on: bool) {
+        if on != self.on {
+            self.on = on;
+            // The receiver only goes away with the audio device, at which point nobody is listening anyway
+            let _ = self.sender.send(SoundEvent { frame: self.frame, on });
+        }
+    }
+}
+
+pub struct SoundTimeline {
+    events: Receiver<SoundEvent>,
+    pending: Option<SoundEvent>,
+    sample_rate: u64,
+    cursor: i64,
+    offset: i64,
+}
+
+impl SoundTimeline {
+    fn new(events: Receiver<SoundEvent>, sample_rate: u32) -> Self {
+        Self {
+            events,
+            pending: None,
+            sample_rate: sample_rate as u64,
+            cursor: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn render(&mut self, out: &mut [f32], tone: &mut Tone) {
+        for x in out.iter_mut() {
+            while let Some(event) = self.peek() {
+                let mut at = self.sample_at(event.frame) + self.offset;
+
+                if at < self.cursor {
+                    // The event arrived after its time was rendered. Shift the timeline so that the
+                    // distance to the next events, and therefore the beep length, is kept intact.
+                    self.offset += self.cursor - at;
+                    at = self.cursor;
+                } else if event.on && tone.is_silent() && at > self.cursor + self.max_latency() {
+                    // Emulation got ahead of playback. Catch up while nothing is playing.
+                    let target = self.cursor + self.max_latency();
+                    self.offset -= at - target;
+                    at = target;
+                }
+
+                if at > self.cursor {
+                    break;
+                }
+
+                tone.set_gate(event.on);
+                self.pending = None;
+            }
+
+            *x = tone.next_sample();
+            self.cursor += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<SoundEvent> {
+        if self.pending.is_none() {
+            self.pending = self.events.try_recv().ok();
+        }
+
+        self.pending
+    }
+
+    fn sample_at(&self, frame: u64) -> i64 {
+        (frame * self.sample_rate / TIMER_HZ) as i64
+    }
+
+    fn max_latency(&self) -> i64 {
+        self.sample_at(MAX_LATENCY_FRAMES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tone::{AudioSettings, Waveform};
+
+    const RATE: u32 = 6000;
+    const SAMPLES_PER_FRAME: usize = RATE as usize / TIMER_HZ as usize;
+
+    fn tone() -> Tone {
+        Tone::new(RATE, AudioSettings { waveform: Waveform::Square, ..AudioSettings::default() })
+    }
+
+    fn ramp_len() -> usize {
+        let mut tone = tone();
+        tone.set_gate(true);
+        (0..RATE).take_while(|_| tone.next_sample().abs() < tone.settings.volume).count()
+    }
+
+    // Length of each run of audible samples
+    fn beeps(samples: &[f32]) -> Vec<usize> {
+        samples
+            .split(|x| *x == 0.0)
+            .map(|run| run.len())
+            .filter(|len| *len > 0)
+            .collect()
+    }
+
+    #[test]
+    fn test_beep_length() {
+        for frames in [1u8, 2, 5, 30] {
+            let (mut clock, mut timeline) = channel(RATE);
+            let mut tone = tone();
+
+            clock.end_frame(0);
+            for st in (1..=frames).rev() {
+                clock.end_frame(st);
+            }
+            for _ in 0..10 {
+                clock.end_frame(0);
+            }
+
+            let mut out = vec![0.0; SAMPLES_PER_FRAME * (frames as usize + 20)];
+            timeline.render(&mut out, &mut tone);
+
+            assert_eq!(beeps(&out), [frames as usize * SAMPLES_PER_FRAME + ramp_len()]);
+            assert_eq!(out.iter().position(|x| *x != 0.0), Some(SAMPLES_PER_FRAME));
+        }
+    }
+
+    #[test]
+    fn test_small_buffers() {
+        let (mut clock, mut timeline) = channel(RATE);
+        let mut tone = tone();
+
+        for st in [0, 3, 2, 1, 0, 0, 1, 0, 0, 0] {
+            clock.end_frame(st);
+        }
+
+        let mut out = vec![0.0; SAMPLES_PER_FRAME * 12];
+        for chunk in out.chunks_mut(7) {
+            timeline.render(chunk, &mut tone);
+        }
+
+        let expected = [3 * SAMPLES_PER_FRAME + ramp_len(), SAMPLES_PER_FRAME + ramp_len()];
+        assert_eq!(beeps(&out), expected);
+    }
+
+    #[test]
+    fn test_late_events() {
+        let (mut clock, mut timeline) = channel(RATE);
+        let mut tone = tone();
+        let mut out = vec![0.0; SAMPLES_PER_FRAME * 10];
+
+        // Playback runs ahead of emulation before the beep even starts
+        timeline.render(&mut out[..SAMPLES_PER_FRAME * 3], &mut tone);
+
+        clock.end_frame(0);
+        clock.end_frame(4);
+        timeline.render(&mut out[SAMPLES_PER_FRAME * 3..SAMPLES_PER_FRAME * 4], &mut tone);
+
+        for st in [3, 2, 1, 0, 0] {
+            clock.end_frame(st);
+        }
+        timeline.render(&mut out[SAMPLES_PER_FRAME * 4..], &mut tone);
+
+        assert_eq!(beeps(&out), [4 * SAMPLES_PER_FRAME + ramp_len()]);
+    }
+
+    #[test]
+    fn test_catch_up() {
+        let (mut clock, mut timeline) = channel(RATE);
+        let mut tone = tone();
+
+        for _ in 0..100 {
+            clock.end_frame(0);
+        }
+        clock.end_frame(2);
+        clock.end_frame(1);
+        clock.end_frame(0);
+
+        let mut out = vec![0.0; SAMPLES_PER_FRAME * 12];
+        timeline.render(&mut out, &mut tone);
+
+        let start = out.iter().position(|x| *x != 0.0).unwrap();
+        assert_eq!(start, MAX_LATENCY_FRAMES as usize * SAMPLES_PER_FRAME);
+        assert_eq!(beeps(&out), [2 * SAMPLES_PER_FRAME + ramp_len()]);
+    }
+
+    #[test]
+    fn test_silence() {
+        let (mut clock, mut timeline) = channel(RATE);
+        let mut tone = tone();
+
+        clock.end_frame(10);
+        clock.silence();
+
+        let mut out = vec![0.0; SAMPLES_PER_FRAME * 4];
+        timeline.render(&mut out, &mut tone);
+        assert_eq!(beeps(&out), [SAMPLES_PER_FRAME + ramp_len()]);
+    }
+}
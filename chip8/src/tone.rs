@@ -0,0 +1,242 @@
+use std::f32::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+pub const MIN_FREQUENCY: f32 = 55.0;
+pub const MAX_FREQUENCY: f32 = 3520.0;
+
+const RAMP_SECONDS: f32 = 0.005;
+const BUZZER_CUTOFF: f32 = 2000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+    Buzzer,
+}
+
+impl Waveform {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Square => Self::Triangle,
+            Self::Triangle => Self::Sine,
+            Self::Sine => Self::Buzzer,
+            Self::Buzzer => Self::Square,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Square => "Square",
+            Self::Triangle => "Triangle",
+            Self::Sine => "Sine",
+            Self::Buzzer => "Buzzer",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.25,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn adjust_volume(&mut self, delta: f32) {
+        self.volume = ((self.volume + delta) * 100.0).round().clamp(0.0, 100.0) / 100.0;
+    }
+
+    pub fn adjust_pitch(&mut self, semitones: i32) {
+        let frequency = self.frequency * 2f32.powf(semitones as f32 / 12.0);
+        self.frequency = frequency.clamp(MIN_FREQUENCY, MAX_FREQUENCY);
+    }
+}
+
+pub struct Tone {
+    pub settings: AudioSettings,
+    sample_rate: f32,
+    phase: f32,
+    gain: f32,
+    ramp_step: f32,
+    gate: bool,
+    filtered: f32,
+    filter_alpha: f32,
+}
+
+impl Tone {
+    pub fn new(sample_rate: u32, settings: AudioSettings) -> Self {
+        let sample_rate = sample_rate as f32;
+        let rc = 1.0 / (TAU * BUZZER_CUTOFF);
+
+        Self {
+            settings,
+            sample_rate,
+            phase: 0.0,
+            gain: 0.0,
+            ramp_step: 1.0 / (RAMP_SECONDS * sample_rate).max(1.0),
+            gate: false,
+            filtered: 0.0,
+            filter_alpha: (1.0 / sample_rate) / (rc + 1.0 / sample_rate),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate as u32
+    }
+
+    pub fn set_gate(&mut self, on: bool) {
+        self.gate = on;
+    }
+
+    pub fn is_silent(&self) -> bool {
+        !self.gate && self.gain == 0.0
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let target = if self.gate && !self.settings.muted { 1.0 } else { 0.0 };
+
+        if self.gain < target {
+            self.gain = (self.gain + self.ramp_step).min(target);
+        } else if self.gain > target {
+            self.gain = (self.gain - self.ramp_step).max(target);
+        }
+
+        if self.gain == 0.0 {
+            self.phase = 0.0;
+            self.filtered = 0.0;
+            return 0.0;
+        }
+
+        let square = if self.phase < 0.5 { 1.0 } else { -1.0 };
+
+        let value = match self.settings.waveform {
+            Waveform::Square => square,
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Sine => (self.phase * TAU).sin(),
+            Waveform::Buzzer => {
+                self.filtered += (square - self.filtered) * self.filter_alpha;
+                self.filtered
+            }
+        };
+
+        self.phase = (self.phase + self.settings.frequency / self.sample_rate) % 1.0;
+        value * self.gain * self.settings.volume
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            *x = self.next_sample();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44100;
+
+    fn tone(waveform: Waveform) -> Tone {
+        let mut tone = Tone::new(RATE, AudioSettings { waveform, ..AudioSettings::default() });
+        tone.set_gate(true);
+        tone
+    }
+
+    fn rising_crossings(samples: &[f32]) -> usize {
+        samples.windows(2).filter(|w| w[0] <= 0.0 && w[1] > 0.0).count()
+    }
+
+    #[test]
+    fn test_silent_by_default() {
+        let mut tone = Tone::new(RATE, AudioSettings::default());
+        let mut out = [1.0; 256];
+        tone.fill(&mut out);
+
+        assert!(out.iter().all(|x| *x == 0.0));
+        assert!(tone.is_silent());
+    }
+
+    #[test]
+    fn test_frequency() {
+        for waveform in [Waveform::Square, Waveform::Triangle, Waveform::Sine, Waveform::Buzzer] {
+            let mut tone = tone(waveform);
+            let mut out = vec![0.0; RATE as usize];
+            tone.fill(&mut out);
+
+            let crossings = rising_crossings(&out) as i32;
+            assert!((crossings - 440).abs() <= 2, "{:?}: {}", waveform, crossings);
+        }
+    }
+
+    #[test]
+    fn test_volume() {
+        let mut tone = tone(Waveform::Square);
+        tone.settings.volume = 0.5;
+
+        let mut out = vec![0.0; 4096];
+        tone.fill(&mut out);
+
+        let peak = out.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+        assert!((peak - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_mute() {
+        let mut tone = tone(Waveform::Square);
+        tone.settings.muted = true;
+
+        let mut out = vec![1.0; 1024];
+        tone.fill(&mut out);
+        assert!(out.iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn test_envelope_ramp() {
+        let mut tone = tone(Waveform::Square);
+        let ramp = (RATE as f32 * RAMP_SECONDS) as usize;
+
+        let mut out = vec![0.0; ramp * 4];
+        tone.fill(&mut out);
+
+        assert!(out[0].abs() < 0.01);
+        assert!(out.windows(2).take(ramp).all(|w| (w[1] - w[0]).abs() < 0.5));
+
+        tone.set_gate(false);
+        tone.fill(&mut out);
+        assert!(out[0].abs() > 0.2);
+        assert_eq!(out[ramp + 1], 0.0);
+        assert!(tone.is_silent());
+    }
+
+    #[test]
+    fn test_adjust() {
+        let mut settings = AudioSettings::default();
+        settings.adjust_pitch(12);
+        assert!((settings.frequency - 880.0).abs() < 0.01);
+
+        settings.adjust_pitch(-1000);
+        assert_eq!(settings.frequency, MIN_FREQUENCY);
+
+        settings.adjust_volume(-1.0);
+        assert_eq!(settings.volume, 0.0);
+
+        settings.adjust_volume(0.05);
+        assert_eq!(settings.volume, 0.05);
+    }
+}
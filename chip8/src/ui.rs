@@ -0,0 +1,124 @@
+use sdl2::pixels::Color;
+
+use crate::text::{self, Surface, GLYPH_HEIGHT, LINE_HEIGHT};
+
+const MARGIN: usize = 4;
+const BACKDROP: Color = Color::RGBA(0, 0, 0, 220);
+const TEXT: Color = Color::RGB(220, 220, 220);
+const HIGHLIGHT: Color = Color::RGB(70, 70, 110);
+const ERROR: Color = Color::RGBA(140, 20, 20, 230);
+
+pub struct Menu {
+    title: String,
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(title: &str, items: Vec<String>) -> Self {
+        Self { title: title.to_string(), items, selected: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        (!self.items.is_empty()).then_some(self.selected)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        }
+    }
+
+    pub fn draw(&self, surface: &mut Surface) {
+        let (width, height) = (surface.width(), surface.height());
+        let max_chars = text::max_chars(width - MARGIN * 2);
+
+        surface.fill_rect(0, 0, width, height, BACKDROP);
+        surface.draw_text(MARGIN, MARGIN, &text::truncate(&self.title, max_chars), Color::WHITE);
+
+        let top = MARGIN + LINE_HEIGHT + MARGIN;
+        let visible = (height - top) / LINE_HEIGHT;
+
+        if self.items.is_empty() {
+            surface.draw_text(MARGIN, top, "(empty)", TEXT);
+            return;
+        }
+
+        let first = self.selected.saturating_sub(visible - 1);
+
+        for (row, (i, item)) in self.items.iter().enumerate().skip(first).take(visible).enumerate() {
+            let y = top + row * LINE_HEIGHT;
+
+            if i == self.selected {
+                surface.fill_rect(0, y - 1, width, GLYPH_HEIGHT + 2, HIGHLIGHT);
+            }
+
+            surface.draw_text(MARGIN, y, &text::truncate(item, max_chars), TEXT);
+        }
+    }
+}
+
+pub fn draw_error(surface: &mut Surface, message: &str) {
+    let width = surface.width();
+    let lines = text::wrap(message, text::max_chars(width - MARGIN * 2));
+    let box_height = lines.len() * LINE_HEIGHT + MARGIN * 2 - 2;
+    let top = surface.height().saturating_sub(box_height);
+
+    surface.fill_rect(0, top, width, box_height, ERROR);
+
+    for (i, line) in lines.iter().enumerate() {
+        surface.draw_text(MARGIN, top + MARGIN + i * LINE_HEIGHT, line, Color::WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection() {
+        let mut menu = Menu::new("Recent", vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        assert_eq!(menu.selected(), Some(0));
+
+        menu.select_previous();
+        assert_eq!(menu.selected(), Some(2));
+
+        menu.select_next();
+        menu.select_next();
+        assert_eq!(menu.selected(), Some(1));
+        assert_eq!(Menu::new("Empty", Vec::new()).selected(), None);
+    }
+
+    #[test]
+    fn test_draw_scrolls() {
+        let items = (0..50).map(|i| format!("item {}", i)).collect();
+        let mut menu = Menu::new("Recent", items);
+        let mut data = vec![0; 64 * 64 * 3];
+
+        for _ in 0..40 {
+            menu.select_next();
+        }
+
+        // Must not panic when the selection is below the visible rows
+        menu.draw(&mut Surface::new(&mut data, 64, 64));
+        assert!(data.iter().any(|x| *x != 0));
+    }
+}
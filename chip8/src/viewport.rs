@@ -0,0 +1,48 @@
+use sdl2::rect::Rect;
+
+pub fn viewport(output: (u32, u32), content: (u32, u32), integer_scaling: bool) -> Rect {
+    let (output_width, output_height) = output;
+    let (content_width, content_height) = content;
+
+    let (width, height) = if integer_scaling {
+        let scale = (output_width / content_width).min(output_height / content_height).max(1);
+        (content_width * scale, content_height * scale)
+    } else if output_width * content_height > output_height * content_width {
+        (output_height * content_width / content_height, output_height)
+    } else {
+        (output_width, output_width * content_height / content_width)
+    };
+
+    let x = (output_width as i32 - width as i32) / 2;
+    let y = (output_height as i32 - height as i32) / 2;
+    Rect::new(x, y, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_fit() {
+        assert_eq!(viewport((960, 480), (64, 32), false), Rect::new(0, 0, 960, 480));
+        assert_eq!(viewport((960, 480), (64, 32), true), Rect::new(0, 0, 960, 480));
+    }
+
+    #[test]
+    fn test_letterbox() {
+        assert_eq!(viewport((1000, 1000), (64, 32), false), Rect::new(0, 250, 1000, 500));
+        assert_eq!(viewport((1920, 600), (64, 32), false), Rect::new(360, 0, 1200, 600));
+    }
+
+    #[test]
+    fn test_integer_scaling() {
+        assert_eq!(viewport((1000, 1000), (64, 32), true), Rect::new(20, 260, 960, 480));
+        assert_eq!(viewport((1000, 1000), (128, 64), true), Rect::new(52, 276, 896, 448));
+    }
+
+    #[test]
+    fn test_too_small() {
+        assert_eq!(viewport((40, 20), (64, 32), true), Rect::new(-12, -6, 64, 32));
+        assert_eq!(viewport((40, 20), (64, 32), false), Rect::new(0, 0, 40, 20));
+    }
+}
@@ -0,0 +1,118 @@
+use std::io::{self, Write};
+
+use core::{Emulator, RandomSource, Result};
+use crate::timeline::{self, TIMER_HZ};
+use crate::tone::{AudioSettings, Tone};
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+pub fn render<R: RandomSource>(
+    emulator: &mut Emulator<R>,
+    frames: u64,
+    ips: u32,
+    settings: AudioSettings,
+    sample_rate: u32,
+) -> Result<Vec<f32>> {
+    let (mut clock, mut timeline) = timeline::channel(sample_rate);
+    let mut tone = Tone::new(sample_rate, settings);
+    let sample_at = |frame: u64| (frame * sample_rate as u64 / TIMER_HZ) as usize;
+
+    let mut samples = vec![0.0; sample_at(frames)];
+    // 700 IPS is not a whole number of cycles per frame, so spread the remainder over the frames
+    let cycles_at = |frame: u64| frame * ips as u64 / TIMER_HZ;
+
+    for frame in 0..frames {
+        emulator.time_step();
+        emulator.cycle((cycles_at(frame + 1) - cycles_at(frame)) as u32)?;
+        clock.end_frame(emulator.sound_timer());
+
+        timeline.render(&mut samples[sample_at(frame)..sample_at(frame + 1)], &mut tone);
+    }
+
+    Ok(samples)
+}
+
+pub fn write_wav<W: Write>(mut writer: W, samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    let data_len = samples.len() as u32 * 2;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&1u16.to_le_bytes())?; // mono
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * 2).to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_all(&value.to_le_bytes())?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Beeps for 6 frames, then spins forever
+    const PROGRAM: [u8; 6] = [
+        0x60, 0x06, // LD V0, 6
+        0xF0, 0x18, // LD ST, V0
+        0x12, 0x04, // JP 0x204
+    ];
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let mut emulator = Emulator::with_seed(0);
+        emulator.load_program(&PROGRAM);
+
+        let samples = render(&mut emulator, 30, 600, AudioSettings::default(), 6000)?;
+        assert_eq!(samples.len(), 3000);
+
+        let first = samples.iter().position(|x| *x != 0.0).unwrap();
+        let last = samples.iter().rposition(|x| *x != 0.0).unwrap();
+        assert_eq!(first, 0);
+        assert!(last > 600 && last < 700);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_fractional_ips() -> Result<()> {
+        // Adds 1 to I every other instruction
+        let program = [
+            0x61, 0x01, // LD V1, 1
+            0xF1, 0x1E, // ADD I, V1
+            0x12, 0x02, // JP 0x202
+        ];
+
+        let mut emulator = Emulator::with_seed(0);
+        emulator.load_program(&program);
+
+        render(&mut emulator, 60, 700, AudioSettings::default(), 6000)?;
+        assert_eq!(emulator.registers().i, 350);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_wav() -> io::Result<()> {
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &[0.0, 1.0, -1.0], 8000)?;
+
+        assert_eq!(bytes.len(), 44 + 6);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &42u32.to_le_bytes());
+        assert_eq!(&bytes[24..28], &8000u32.to_le_bytes());
+        assert_eq!(&bytes[40..44], &6u32.to_le_bytes());
+        assert_eq!(&bytes[44..], [0x00, 0x00, 0xFF, 0x7F, 0x01, 0x80]);
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use chip8::dap::DapServer;
+use core::Emulator;
+
+fn rom(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms").join(name)
+}
+
+struct Session {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+    seq: u64,
+    events: VecDeque<Value>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl Session {
+    fn start() -> Self {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut server = DapServer::accept(&listener).unwrap();
+            let mut emulator = Emulator::with_seed(0);
+
+            while !server.is_terminated() {
+                if let Some(path) = server.poll(&mut emulator).unwrap() {
+                    emulator.reset();
+                    emulator.load_program(&fs::read(path).unwrap());
+                }
+
+                server.cycle(&mut emulator, 10).unwrap();
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        Self { stream, reader, seq: 0, events: VecDeque::new(), server: Some(server) }
+    }
+
+    /// Initializes the session and launches the test ROM, with its symbols next to it
+    fn launch(stop_on_entry: bool) -> Self {
+        let mut session = Self::start();
+        session.request("initialize", json!({ "adapterID": "chip8" }));
+
+        let program = rom("dap.ch8");
+        let response = session.request("launch", json!({ "program": program, "stopOnEntry": stop_on_entry }));
+        assert_eq!(response["success"], true);
+
+        session.event("initialized");
+        session
+    }
+
+    fn send(&mut self, command: &str, arguments: Value) -> u64 {
+        self.seq += 1;
+        let body = json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments }).to_string();
+        write!(self.stream, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.seq
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+
+            match line.trim_end().strip_prefix("Content-Length: ") {
+                Some(value) => length = value.parse().unwrap(),
+                None if line.trim_end().is_empty() => break,
+                None => panic!("unexpected header: {:?}", line),
+            }
+        }
+
+        let mut body = vec![0; length];
+        self.reader.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let seq = self.send(command, arguments);
+
+        loop {
+            let message = self.receive();
+
+            if message["type"] == "response" {
+                assert_eq!((&message["request_seq"], &message["command"]), (&json!(seq), &json!(command)));
+                return message;
+            }
+
+            self.events.push_back(message);
+        }
+    }
+
+    fn event(&mut self, name: &str) -> Value {
+        if let Some(index) = self.events.iter().position(|event| event["event"] == name) {
+            return self.events.remove(index).unwrap()["body"].clone();
+        }
+
+        loop {
+            let message = self.receive();
+
+            if message["event"] == name {
+                return message["body"].clone();
+            }
+
+            self.events.push_back(message);
+        }
+    }
+
+    fn stopped(&mut self) -> String {
+        let body = self.event("stopped");
+        assert_eq!(body["threadId"], 1);
+        body["reason"].as_str().unwrap().to_string()
+    }
+
+    fn frames(&mut self) -> Vec<Value> {
+        let response = self.request("stackTrace", json!({ "threadId": 1 }));
+        response["body"]["stackFrames"].as_array().unwrap().clone()
+    }
+
+    fn pc(&mut self) -> String {
+        self.frames()[0]["instructionPointerReference"].as_str().unwrap().to_string()
+    }
+
+    fn variable(&mut self, reference: u64, name: &str) -> String {
+        let response = self.request("variables", json!({ "variablesReference": reference }));
+        let variables = response["body"]["variables"].as_array().unwrap();
+        let variable = variables.iter().find(|variable| variable["name"] == name).unwrap();
+        variable["value"].as_str().unwrap().to_string()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+
+        if let Some(server) = self.server.take() {
+            server.join().unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_launch() {
+    let mut session = Session::start();
+    let response = session.request("initialize", json!({ "adapterID": "chip8" }));
+    assert_eq!(response["body"]["supportsConfigurationDoneRequest"], true);
+
+    let response = session.request("launch", json!({ "program": rom("missing.ch8") }));
+    assert_eq!(response["success"], false);
+
+    let response = session.request("launch", json!({ "program": rom("dap.ch8") }));
+    assert_eq!(response["success"], true);
+    session.event("initialized");
+
+    let response = session.request("threads", Value::Null);
+    assert_eq!(response["body"]["threads"], json!([{ "id": 1, "name": "CHIP-8" }]));
+}
+
+#[test]
+fn test_line_breakpoints() {
+    let mut session = Session::launch(false);
+    let source = rom("dap.8o");
+
+    let response = session.request("setBreakpoints", json!({ "source": { "path": source }, "breakpoints": [{ "line": 9 }, { "line": 7 }, { "line": 20 }] }));
+    let breakpoints = &response["body"]["breakpoints"];
+    assert_eq!((&breakpoints[0]["verified"], &breakpoints[0]["line"]), (&json!(true), &json!(9)));
+    assert_eq!((&breakpoints[1]["verified"], &breakpoints[1]["line"]), (&json!(true), &json!(9)));
+    assert_eq!(breakpoints[2]["verified"], false);
+
+    session.request("configurationDone", Value::Null);
+    assert_eq!(session.stopped(), "breakpoint");
+
+    let frames = session.frames();
+    assert_eq!(frames.len(), 2);
+    assert_eq!((&frames[0]["name"], &frames[0]["line"]), (&json!("setup"), &json!(9)));
+    assert_eq!((&frames[1]["name"], &frames[1]["line"]), (&json!("main"), &json!(3)));
+    assert_eq!(frames[1]["instructionPointerReference"], "0x202");
+    assert_eq!(frames[0]["source"]["path"], json!(source));
+
+    let response = session.request("scopes", json!({ "frameId": 0 }));
+    assert_eq!(response["body"]["scopes"][0]["name"], "Registers");
+    assert_eq!(session.variable(1, "V0"), "0x05");
+    assert_eq!(session.variable(1, "PC"), "0x208");
+    assert_eq!(session.variable(1, "SP"), "1");
+    assert_eq!(session.variable(2, "DT"), "0");
+}
+
+#[test]
+fn test_entry_breakpoint() {
+    let mut session = Session::launch(false);
+    let response = session.request("setBreakpoints", json!({ "source": { "path": rom("dap.8o") }, "breakpoints": [{ "line": 2 }] }));
+    assert_eq!(response["body"]["breakpoints"][0]["instructionReference"], "0x200");
+
+    session.request("configurationDone", Value::Null);
+    assert_eq!(session.stopped(), "breakpoint");
+    assert_eq!(session.pc(), "0x200");
+    assert_eq!(session.variable(1, "V0"), "0x00");
+
+    // Resuming runs the instruction with the breakpoint instead of stopping on it again
+    session.request("setBreakpoints", json!({ "source": { "path": rom("dap.8o") }, "breakpoints": [{ "line": 2 }, { "line": 9 }] }));
+    session.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(session.stopped(), "breakpoint");
+    assert_eq!(session.pc(), "0x208");
+}
+
+#[test]
+fn test_step_over() {
+    let mut session = Session::launch(true);
+    session.request("configurationDone", Value::Null);
+    assert_eq!(session.stopped(), "entry");
+    assert_eq!(session.pc(), "0x200");
+
+    session.request("next", json!({ "threadId": 1 }));
+    assert_eq!(session.stopped(), "step");
+    assert_eq!(session.frames()[0]["line"], 3);
+
+    session.request("next", json!({ "threadId": 1 }));
+    assert_eq!(session.stopped(), "step");
+    assert_eq!(session.pc(), "0x204");
+    assert_eq!(session.variable(1, "V1"), "0x07");
+}
+
+#[test]
+fn test_step_in_and_out() {
+    let mut session = Session::launch(true);
+    session.request("configurationDone", Value::Null);
+    assert_eq!(session.stopped(), "entry");
+
+    session.request("next", json!({ "threadId": 1 }));
+    assert_eq!(session.stopped(), "step");
+
+    session.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(session.stopped(), "step");
+    assert_eq!(session.frames().len(), 2);
+    assert_eq!(session.pc(), "0x208");
+
+    session.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(session.stopped(), "step");
+    assert_eq!(session.frames().len(), 1);
+    assert_eq!(session.pc(), "0x204");
+}
+
+#[test]
+fn test_instruction_breakpoints() {
+    let mut session = Session::launch(false);
+
+    let breakpoints = json!([
+        { "instructionReference": "0x204" },
+        { "instructionReference": "0x200", "offset": 6 },
+        { "instructionReference": "label" },
+    ]);
+
+    let response = session.request("setInstructionBreakpoints", json!({ "breakpoints": breakpoints }));
+    let breakpoints = &response["body"]["breakpoints"];
+    assert_eq!((&breakpoints[0]["verified"], &breakpoints[1]["verified"]), (&json!(true), &json!(true)));
+    assert_eq!(breakpoints[1]["instructionReference"], "0x206");
+    assert_eq!(breakpoints[2]["verified"], false);
+
+    session.request("configurationDone", Value::Null);
+    assert_eq!(session.stopped(), "breakpoint");
+    assert_eq!(session.pc(), "0x204");
+
+    session.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(session.stopped(), "breakpoint");
+    assert_eq!(session.pc(), "0x206");
+    assert_eq!(session.variable(1, "V0"), "0x06");
+
+    session.request("setInstructionBreakpoints", json!({ "breakpoints": [] }));
+    session.request("continue", json!({ "threadId": 1 }));
+    thread::sleep(Duration::from_millis(20));
+    session.request("pause", json!({ "threadId": 1 }));
+    assert_eq!(session.stopped(), "pause");
+}
+
+#[test]
+fn test_unsupported_command() {
+    let mut session = Session::launch(false);
+    let response = session.request("evaluate", json!({ "expression": "v0" }));
+    assert_eq!(response["success"], false);
+    assert_eq!(response["message"], "unsupported command: evaluate");
+}
+
+#[test]
+fn test_disconnect() {
+    let mut session = Session::launch(false);
+    assert_eq!(session.request("disconnect", json!({ "terminateDebuggee": true }))["success"], true);
+    session.server.take().unwrap().join().unwrap();
+}
@@ -0,0 +1,239 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chip8::gdb::GdbStub;
+use core::Emulator;
+
+// 200: LD V0, 5
+// 202: ADD V0, 1
+// 204: JP 202
+const PROGRAM: [u8; 6] = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+
+struct Session {
+    stream: TcpStream,
+    stop: Arc<AtomicBool>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl Session {
+    fn start() -> Self {
+        let mut stub = GdbStub::bind(0).unwrap();
+        let addr = stub.local_addr().unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let server = {
+            let stop = stop.clone();
+
+            thread::spawn(move || {
+                let mut emulator = Emulator::with_seed(0);
+                emulator.reset();
+                emulator.load_program(&PROGRAM);
+
+                while !stop.load(Ordering::Relaxed) {
+                    stub.poll(&mut emulator).unwrap();
+
+                    if stub.is_attached() {
+                        stub.cycle(&mut emulator, 10).unwrap();
+                    }
+
+                    thread::sleep(Duration::from_millis(1));
+                }
+            })
+        };
+
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        Self { stream, stop, server: Some(server) }
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, checksum).unwrap();
+    }
+
+    fn receive(&mut self) -> String {
+        let mut packet = Vec::new();
+        let mut byte = [0];
+
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+
+            match byte[0] {
+                b'+' if packet.is_empty() => continue,
+                b'#' => break,
+                _ => packet.push(byte[0]),
+            }
+        }
+
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+
+        String::from_utf8(packet[1..].to_vec()).unwrap()
+    }
+
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        self.receive()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(server) = self.server.take() {
+            server.join().unwrap();
+        }
+    }
+}
+
+fn register(registers: &str, range: std::ops::Range<usize>) -> &str {
+    &registers[range.start * 2..range.end * 2]
+}
+
+#[test]
+fn test_halts_on_attach() {
+    let mut session = Session::start();
+    assert_eq!(session.request("?"), "S05");
+
+    let registers = session.request("g");
+    assert_eq!(registers.len(), 23 * 2);
+    assert_eq!(register(&registers, 0..1), "00");
+    assert_eq!(register(&registers, 18..20), "0002");
+    assert_eq!(session.request("g"), registers);
+}
+
+#[test]
+fn test_step() {
+    let mut session = Session::start();
+    assert_eq!(session.request("s"), "S05");
+    assert_eq!(session.request("p0"), "05");
+    assert_eq!(session.request("s"), "S05");
+    assert_eq!(session.request("p0"), "06");
+    assert_eq!(session.request("p11"), "0402");
+}
+
+#[test]
+fn test_write_registers() {
+    let mut session = Session::start();
+    assert_eq!(session.request("P0=2a"), "OK");
+    assert_eq!(session.request("P10=3402"), "OK");
+    assert_eq!(session.request("p0"), "2a");
+    assert_eq!(session.request("p10"), "3402");
+
+    let mut registers = session.request("g");
+    registers.replace_range(36..40, "0402");
+    assert_eq!(session.request(&format!("G{}", registers)), "OK");
+    assert_eq!(session.request("p11"), "0402");
+
+    assert_eq!(session.request("P15=00"), "E01");
+    assert_eq!(session.request("G00"), "E01");
+}
+
+#[test]
+fn test_write_registers_out_of_range() {
+    let mut session = Session::start();
+    assert_eq!(session.request("P11=0010"), "E01");
+    assert_eq!(session.request("P10=0010"), "E01");
+    assert_eq!(session.request("P12=01"), "E01");
+    assert_eq!(session.request("s1000"), "E01");
+
+    let mut registers = session.request("g");
+    registers.replace_range(40..42, "01");
+    assert_eq!(session.request(&format!("G{}", registers)), "E01");
+    assert_eq!(session.request("p11"), "0002");
+}
+
+#[test]
+fn test_step_at_end_of_memory() {
+    let mut session = Session::start();
+    assert_eq!(session.request("P11=ff0f"), "OK");
+    assert_eq!(session.request("s"), "S05");
+    assert_eq!(session.request("p11"), "0100");
+    assert_eq!(session.request("sfff"), "S05");
+    assert_eq!(session.request("p11"), "0100");
+}
+
+#[test]
+fn test_memory() {
+    let mut session = Session::start();
+    assert_eq!(session.request("m200,6"), "600570011202");
+    assert_eq!(session.request("M300,2:abcd"), "OK");
+    assert_eq!(session.request("m300,2"), "abcd");
+    assert_eq!(session.request("mffe,4"), "0000");
+    assert_eq!(session.request("m1000,1"), "E01");
+    assert_eq!(session.request("Mfff,2:0000"), "E01");
+}
+
+#[test]
+fn test_length_overflow() {
+    let mut session = Session::start();
+    assert_eq!(session.request("m200,ffffffffffffffff"), "E01");
+    assert_eq!(session.request("Mffffffffffffffff,2:0000"), "E01");
+    assert_eq!(session.request("qXfer:features:read:target.xml:10,ffffffffffffffff"), "E01");
+    assert_eq!(session.request("m200,2"), "6005");
+}
+
+#[test]
+fn test_breakpoint() {
+    let mut session = Session::start();
+    assert_eq!(session.request("Z0,204,2"), "OK");
+
+    session.send("c");
+    assert_eq!(session.receive(), "T05swbreak:;");
+    assert_eq!(session.request("p11"), "0402");
+    assert_eq!(session.request("p0"), "06");
+
+    session.send("c");
+    assert_eq!(session.receive(), "T05swbreak:;");
+    assert_eq!(session.request("p0"), "07");
+
+    assert_eq!(session.request("z0,204,2"), "OK");
+    assert_eq!(session.request("Z1,204,2"), "");
+}
+
+#[test]
+fn test_interrupt() {
+    let mut session = Session::start();
+    session.send("c");
+    thread::sleep(Duration::from_millis(20));
+    session.stream.write_all(&[0x03]).unwrap();
+
+    assert_eq!(session.receive(), "S02");
+    assert!(["0202", "0402"].contains(&session.request("p11").as_str()));
+}
+
+#[test]
+fn test_target_description() {
+    let mut session = Session::start();
+    assert!(session.request("qSupported:swbreak+").contains("qXfer:features:read+"));
+
+    let first = session.request("qXfer:features:read:target.xml:0,20");
+    assert_eq!(first, "m<?xml version=\"1.0\"?>\n<!DOCTYPE ");
+
+    let full = session.request("qXfer:features:read:target.xml:0,ffff");
+    assert!(full.starts_with('l') && full.contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>"));
+}
+
+#[test]
+fn test_no_ack_mode() {
+    let mut session = Session::start();
+    assert_eq!(session.request("QStartNoAckMode"), "OK");
+    assert_eq!(session.request("p0"), "00");
+}
+
+#[test]
+fn test_bad_checksum() {
+    let mut session = Session::start();
+    session.stream.write_all(b"$g#00").unwrap();
+
+    let mut nack = [0];
+    session.stream.read_exact(&mut nack).unwrap();
+    assert_eq!(&nack, b"-");
+    assert_eq!(session.request("p0"), "00");
+}
@@ -0,0 +1,232 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::{Emulator, Error, RandomSource, Result};
+use crate::hash::rom_hash;
+
+const MAGIC: &str = "chip8-cheats 1";
+const MEMORY_SIZE: usize = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            Self::Equal(value) => current == value,
+            Self::Changed => current != previous,
+            Self::Unchanged => current == previous,
+            Self::Increased => current > previous,
+            Self::Decreased => current < previous,
+        }
+    }
+}
+
+/// An iterative memory search. Every filter keeps the candidate addresses whose value matches the comparison against
+/// the value seen by the previous filter, or when the search started.
+#[derive(Clone)]
+pub struct Search {
+    candidates: Vec<u16>,
+    previous: [u8; MEMORY_SIZE],
+}
+
+impl Search {
+    pub fn new(memory: &[u8; MEMORY_SIZE]) -> Self {
+        Self { candidates: (0..MEMORY_SIZE as u16).collect(), previous: *memory }
+    }
+
+    pub fn filter(&mut self, memory: &[u8; MEMORY_SIZE], comparison: Comparison) -> usize {
+        let previous = &self.previous;
+        self.candidates.retain(|&addr| comparison.matches(previous[addr as usize], memory[addr as usize]));
+        self.previous = *memory;
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    /// Value of `addr` when the last filter ran
+    pub fn previous(&self, addr: u16) -> u8 {
+        self.previous[addr as usize % MEMORY_SIZE]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub addr: u16,
+    pub value: u8,
+    pub enabled: bool,
+    pub name: String,
+}
+
+/// The cheats of one ROM, which pin memory addresses to fixed values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheats {
+    pub rom_hash: u64,
+    pub cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new(program: &[u8]) -> Self {
+        Self { rom_hash: rom_hash(program), cheats: Vec::new() }
+    }
+
+    pub fn matches_program(&self, program: &[u8]) -> bool {
+        self.rom_hash == rom_hash(program)
+    }
+
+    /// Pins `addr` to `value`, replacing any cheat for the same address
+    pub fn pin(&mut self, addr: u16, value: u8) {
+        match self.cheats.iter_mut().find(|cheat| cheat.addr == addr) {
+            Some(cheat) => {
+                cheat.value = value;
+                cheat.enabled = true;
+            }
+            None => self.cheats.push(Cheat { addr, value, enabled: true, name: String::new() }),
+        }
+    }
+
+    pub fn unpin(&mut self, addr: u16) {
+        self.cheats.retain(|cheat| cheat.addr != addr);
+    }
+
+    /// Writes the values of the enabled cheats, once per frame
+    pub fn apply<R: RandomSource>(&self, emulator: &mut Emulator<R>) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            emulator.memory[cheat.addr as usize % MEMORY_SIZE] = cheat.value;
+        }
+    }
+}
+
+impl Display for Cheats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        writeln!(f, "rom {:016x}", self.rom_hash)?;
+
+        for cheat in &self.cheats {
+            let state = if cheat.enabled { "on" } else { "off" };
+            writeln!(f, "{} {:03x} {:02x} {}", state, cheat.addr, cheat.value, cheat.name)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Cheats {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+
+        match lines.next() {
+            Some((_, line)) if line.trim() == MAGIC => {}
+            other => return Err(Error::InvalidCheats { line: other.map_or(1, |(i, _)| i + 1) }),
+        }
+
+        let mut cheats = Self { rom_hash: 0, cheats: Vec::new() };
+
+        for (i, line) in lines {
+            let invalid = || Error::InvalidCheats { line: i + 1 };
+            let mut parts = line.trim().splitn(4, ' ');
+
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("rom"), Some(hash), None) => cheats.rom_hash = u64::from_str_radix(hash, 16).map_err(|_| invalid())?,
+                (Some(state @ ("on" | "off")), Some(addr), Some(value)) => {
+                    let addr = u16::from_str_radix(addr, 16).ok().filter(|addr| (*addr as usize) < MEMORY_SIZE).ok_or_else(invalid)?;
+                    let value = u8::from_str_radix(value, 16).map_err(|_| invalid())?;
+                    let name = parts.next().unwrap_or_default().trim().to_string();
+                    cheats.cheats.push(Cheat { addr, value, enabled: state == "on", name });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(cheats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XorShift;
+
+    #[test]
+    fn test_search() {
+        let mut memory = [0; MEMORY_SIZE];
+        memory[0x300] = 3;
+        memory[0x301] = 3;
+        memory[0x302] = 7;
+
+        let mut search = Search::new(&memory);
+        assert_eq!(search.filter(&memory, Comparison::Equal(3)), 2);
+
+        memory[0x301] = 2;
+        assert_eq!(search.filter(&memory, Comparison::Decreased), 1);
+        assert_eq!(search.candidates(), [0x301]);
+        assert_eq!(search.previous(0x301), 2);
+
+        assert_eq!(search.filter(&memory, Comparison::Unchanged), 1);
+        assert_eq!(search.filter(&memory, Comparison::Changed), 0);
+    }
+
+    #[test]
+    fn test_increased() {
+        let mut memory = [0; MEMORY_SIZE];
+        let mut search = Search::new(&memory);
+        memory[0x3F0] = 1;
+        memory[0x3F1] = 9;
+
+        assert_eq!(search.filter(&memory, Comparison::Increased), 2);
+        memory[0x3F1] = 8;
+        assert_eq!(search.filter(&memory, Comparison::Changed), 1);
+        assert_eq!(search.candidates(), [0x3F1]);
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut cheats = Cheats::new(&[0x12, 0x00]);
+        cheats.pin(0x3F0, 9);
+        cheats.pin(0x3F1, 1);
+        cheats.pin(0x3F0, 5);
+        cheats.cheats[1].enabled = false;
+
+        let mut emulator = Emulator::<XorShift>::with_seed(0);
+        cheats.apply(&mut emulator);
+        assert_eq!(emulator.memory[0x3F0..0x3F2], [5, 0]);
+
+        cheats.unpin(0x3F0);
+        assert_eq!(cheats.cheats.len(), 1);
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let mut cheats = Cheats::new(&[0x12, 0x00]);
+        cheats.pin(0x3F0, 9);
+        cheats.pin(0x20A, 0xFF);
+        cheats.cheats[0].name = "Infinite lives".to_string();
+        cheats.cheats[1].enabled = false;
+
+        let parsed: Cheats = cheats.to_string().parse()?;
+        assert_eq!(parsed, cheats);
+        assert!(parsed.matches_program(&[0x12, 0x00]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!("hello".parse::<Cheats>(), Err(Error::InvalidCheats { line: 1 })));
+
+        let cheats = format!("{}\nrom 01\non 1000 05\n", MAGIC);
+        assert!(matches!(cheats.parse::<Cheats>(), Err(Error::InvalidCheats { line: 3 })));
+
+        let cheats = format!("{}\nmaybe 300 05\n", MAGIC);
+        assert!(matches!(cheats.parse::<Cheats>(), Err(Error::InvalidCheats { line: 2 })));
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+use crate::{Emulator, Error, Instruction, RandomSource, Result};
+use crate::emulator::PROGRAM_OFFSET;
+use crate::hash::rom_hash;
+
+const MAGIC: &str = "chip8-coverage 1";
+const MEMORY_SIZE: usize = 0x1000;
+
+/// How often each address of one ROM was executed, and read as data by `DRW`, `LD Vx, [I]` and `LD B, Vx`. Coverage
+/// recorded over several runs of the same ROM can be merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    pub rom_hash: u64,
+    code: Vec<u64>,
+    data: Vec<u64>,
+}
+
+impl Coverage {
+    pub fn new(program: &[u8]) -> Self {
+        Self { rom_hash: rom_hash(program), code: vec![0; MEMORY_SIZE], data: vec![0; MEMORY_SIZE] }
+    }
+
+    pub fn matches_program(&self, program: &[u8]) -> bool {
+        self.rom_hash == rom_hash(program)
+    }
+
+    pub fn cycle<R: RandomSource>(&mut self, emulator: &mut Emulator<R>, times: u32) -> Result<()> {
+        for _ in 0..times {
+            let registers = emulator.registers();
+            let pc = registers.pc as usize;
+            let opcode = u16::from_be_bytes([emulator.memory[pc % MEMORY_SIZE], emulator.memory[(pc + 1) % MEMORY_SIZE]]);
+
+            let read = match Instruction::try_from(opcode)? {
+                Instruction::DRW(_, _, n) => n as usize,
+                Instruction::LDVxI(x) => x + 1,
+                Instruction::LDBVx(_) => 3,
+                _ => 0,
+            };
+
+            emulator.cycle(1)?;
+            self.code[pc % MEMORY_SIZE] += 1;
+
+            for offset in 0..read {
+                self.data[(registers.i as usize + offset) % MEMORY_SIZE] += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the counts of `other`, which must be recorded with the same ROM
+    pub fn merge(&mut self, other: &Coverage) -> Result<()> {
+        if other.rom_hash != self.rom_hash {
+            return Err(Error::CoverageMismatch);
+        }
+
+        for (count, other) in self.code.iter_mut().zip(&other.code).chain(self.data.iter_mut().zip(&other.data)) {
+            *count += other;
+        }
+
+        Ok(())
+    }
+
+    pub fn executions(&self, addr: u16) -> u64 {
+        self.code[addr as usize % MEMORY_SIZE]
+    }
+
+    pub fn reads(&self, addr: u16) -> u64 {
+        self.data[addr as usize % MEMORY_SIZE]
+    }
+
+    /// Marks the instructions of `program`: every executed address, and every address reachable from the entry point
+    /// or an executed instruction by following jumps, calls, skips and fall-through. `JP V0` targets can't be followed,
+    /// and addresses only ever read as data stop the search.
+    pub fn code_map(&self, program: &[u8]) -> Vec<bool> {
+        let end = PROGRAM_OFFSET + program.len().min(MEMORY_SIZE - PROGRAM_OFFSET);
+        let mut code = vec![false; MEMORY_SIZE];
+        let mut pending: Vec<usize> = (0..MEMORY_SIZE).filter(|&addr| self.code[addr] > 0).collect();
+        pending.push(PROGRAM_OFFSET);
+
+        while let Some(addr) = pending.pop() {
+            if addr < PROGRAM_OFFSET || addr + 1 >= end || code[addr] || (self.code[addr] == 0 && self.data[addr] > 0) {
+                continue;
+            }
+
+            let opcode = u16::from_be_bytes([program[addr - PROGRAM_OFFSET], program[addr + 1 - PROGRAM_OFFSET]]);
+
+            let next = match Instruction::try_from(opcode) {
+                Ok(instruction) => instruction,
+                Err(_) => continue,
+            };
+
+            code[addr] = true;
+
+            match next {
+                Instruction::JP(target) => pending.push(target),
+                Instruction::CALL(target) => pending.extend([target, addr + 2]),
+                Instruction::RET | Instruction::JPV0(_) => {}
+                Instruction::SEVxKK(..) | Instruction::SNEVxKK(..) | Instruction::SEVxVy(..) | Instruction::SNE(..)
+                | Instruction::SKP(_) | Instruction::SKNP(_) => pending.extend([addr + 2, addr + 4]),
+                _ => pending.push(addr + 2),
+            }
+        }
+
+        code
+    }
+
+    /// Disassembly of `program` annotated like gcov: each instruction with its execution count, or `#####` if it
+    /// never ran, and each data byte with its read count, or `-` if it was never read
+    pub fn listing(&self, program: &[u8]) -> String {
+        let code = self.code_map(program);
+        let end = PROGRAM_OFFSET + program.len().min(MEMORY_SIZE - PROGRAM_OFFSET);
+        let mut listing = String::new();
+        let mut addr = PROGRAM_OFFSET;
+
+        while addr < end {
+            let byte = program[addr - PROGRAM_OFFSET];
+
+            if code[addr] {
+                let opcode = u16::from_be_bytes([byte, program[addr + 1 - PROGRAM_OFFSET]]);
+                let count = match self.code[addr] {
+                    0 => "#####".to_string(),
+                    count => count.to_string(),
+                };
+
+                // Instructions in the code map always decode
+                let instruction = Instruction::try_from(opcode).map(|instruction| instruction.to_string()).unwrap_or_default();
+                let _ = writeln!(listing, "{:>9}:  {:03X}  {:04X}  {}", count, addr, opcode, instruction);
+                addr += 2;
+            } else {
+                let count = match self.data[addr] {
+                    0 => "-".to_string(),
+                    count => count.to_string(),
+                };
+
+                let _ = writeln!(listing, "{:>9}:  {:03X}  {:02X}    db 0x{:02X}", count, addr, byte, byte);
+                addr += 1;
+            }
+        }
+
+        listing
+    }
+
+    /// LCOV tracefile for `program`, named `name`, with the address of each instruction as its line number
+    pub fn lcov(&self, program: &[u8], name: &str) -> String {
+        let lines: Vec<_> = self.code_map(program).into_iter().enumerate().filter(|(_, code)| *code).map(|(addr, _)| addr).collect();
+        let hit = lines.iter().filter(|&&addr| self.code[addr] > 0).count();
+
+        let mut lcov = format!("TN:\nSF:{}\n", name);
+
+        for addr in &lines {
+            let _ = writeln!(lcov, "DA:{},{}", addr, self.code[*addr]);
+        }
+
+        let _ = write!(lcov, "LF:{}\nLH:{}\nend_of_record\n", lines.len(), hit);
+        lcov
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        writeln!(f, "rom {:016x}", self.rom_hash)?;
+
+        for (kind, counts) in [("code", &self.code), ("data", &self.data)] {
+            for (addr, count) in counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+                writeln!(f, "{} {:03x} {}", kind, addr, count)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Coverage {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+
+        match lines.next() {
+            Some((_, line)) if line.trim() == MAGIC => {}
+            other => return Err(Error::InvalidCoverage { line: other.map_or(1, |(i, _)| i + 1) }),
+        }
+
+        let mut coverage = Self { rom_hash: 0, code: vec![0; MEMORY_SIZE], data: vec![0; MEMORY_SIZE] };
+
+        for (i, line) in lines {
+            let invalid = || Error::InvalidCoverage { line: i + 1 };
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            match parts[..] {
+                ["rom", hash] => coverage.rom_hash = u64::from_str_radix(hash, 16).map_err(|_| invalid())?,
+                [kind @ ("code" | "data"), addr, count] => {
+                    let addr = usize::from_str_radix(addr, 16).ok().filter(|addr| *addr < MEMORY_SIZE).ok_or_else(invalid)?;
+                    let count = count.parse().map_err(|_| invalid())?;
+                    let counts = if kind == "code" { &mut coverage.code } else { &mut coverage.data };
+                    counts[addr] = count;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(coverage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XorShift;
+
+    // Draws the sprite at 0x20C once if V0 is 0, then loops forever
+    const PROGRAM: [u8; 16] = [
+        0xA2, 0x0C, // LD I, 0x20C
+        0x40, 0x00, // SNE V0, 0x00
+        0x22, 0x08, // CALL 0x208
+        0x12, 0x06, // JP 0x206
+        0xD1, 0x11, // DRW V1, V1, 1
+        0x00, 0xEE, // RET
+        0xF0, 0x90, // sprite
+        0x00, 0x00,
+    ];
+
+    fn record(v0: u8) -> Result<Coverage> {
+        let mut emulator = Emulator::<XorShift>::with_seed(0);
+        emulator.load_program(&PROGRAM);
+
+        let mut registers = emulator.registers();
+        registers.v[0] = v0;
+        emulator.set_registers(registers);
+
+        let mut coverage = Coverage::new(&PROGRAM);
+        coverage.cycle(&mut emulator, 6)?;
+        Ok(coverage)
+    }
+
+    #[test]
+    fn test_record() -> Result<()> {
+        let coverage = record(1)?;
+        assert_eq!([0x200, 0x202, 0x204, 0x206].map(|addr| coverage.executions(addr)), [1, 1, 0, 4]);
+        assert_eq!(coverage.reads(0x20C), 0);
+
+        let coverage = record(0)?;
+        assert_eq!([0x204, 0x206, 0x208, 0x20A].map(|addr| coverage.executions(addr)), [1, 1, 1, 1]);
+        assert_eq!((coverage.reads(0x20C), coverage.reads(0x20D)), (1, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_listing() -> Result<()> {
+        let listing = record(1)?.listing(&PROGRAM);
+        let lines: Vec<_> = listing.lines().collect();
+
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "        1:  200  A20C  LD I, 0x20C");
+        assert_eq!(lines[2], "    #####:  204  2208  CALL 0x208");
+        assert_eq!(lines[5], "    #####:  20A  00EE  RET");
+        assert_eq!(lines[6], "        -:  20C  F0    db 0xF0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let mut coverage = record(1)?;
+        coverage.merge(&record(0)?)?;
+        assert_eq!(coverage.executions(0x206), 5);
+        assert_eq!(coverage.reads(0x20C), 1);
+
+        let lcov = coverage.lcov(&PROGRAM, "game.ch8");
+        assert!(lcov.starts_with("TN:\nSF:game.ch8\nDA:512,2\n"));
+        assert!(lcov.ends_with("DA:522,1\nLF:6\nLH:6\nend_of_record\n"));
+
+        assert!(matches!(coverage.merge(&Coverage::new(&[0x12, 0x00])), Err(Error::CoverageMismatch)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let coverage = record(0)?;
+        let parsed: Coverage = coverage.to_string().parse()?;
+        assert_eq!(parsed, coverage);
+        assert!(parsed.matches_program(&PROGRAM));
+
+        let invalid = format!("{}\nrom 01\ncode 1000 1\n", MAGIC);
+        assert!(matches!(invalid.parse::<Coverage>(), Err(Error::InvalidCoverage { line: 3 })));
+        Ok(())
+    }
+}
@@ -1,17 +1,77 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+    Clip,
+    Wrap,
+}
+
+#[derive(Clone)]
 pub struct Display {
     pixel_rows: [u64; 32],
+    dirty: u32,
+    version: u64,
 }
 
 impl Display {
+    pub const WIDTH: usize = 64;
+    pub const HEIGHT: usize = 32;
+
     pub fn new() -> Self {
-        Self { pixel_rows: [0; 32] }
+        Self { pixel_rows: [0; 32], dirty: u32::MAX, version: 0 }
+    }
+
+    pub fn width(&self) -> usize {
+        Self::WIDTH
+    }
+
+    pub fn height(&self) -> usize {
+        Self::HEIGHT
     }
 
     pub fn toggle(&mut self, x: u8, y: u8) -> bool {
+        let (x, y) = (x as usize % Self::WIDTH, y as usize % Self::HEIGHT);
         let mask: u64 = 1 << (63 - x);
-        self.pixel_rows[y as usize] ^= mask;
+        self.pixel_rows[y] ^= mask;
+        self.mark_changed(1 << y);
 
-        self.pixel_rows[y as usize] & mask != 0
+        self.pixel_rows[y] & mask != 0
+    }
+
+    /// XORs a sprite onto the display and returns the number of rows in which a lit pixel was turned off
+    pub fn draw_sprite(&mut self, x: usize, y: usize, bytes: &[u8], mode: EdgeMode) -> usize {
+        let x = x % Self::WIDTH;
+        let y = y % Self::HEIGHT;
+        let mut collisions = 0;
+        let mut changed = 0;
+
+        for (i, byte) in bytes.iter().enumerate() {
+            let row = y + i;
+
+            if row >= Self::HEIGHT && mode == EdgeMode::Clip {
+                break;
+            }
+
+            let sprite = (*byte as u64) << 56;
+            let mask = match mode {
+                EdgeMode::Clip => sprite >> x,
+                EdgeMode::Wrap => sprite.rotate_right(x as u32),
+            };
+
+            let pixels = &mut self.pixel_rows[row % Self::HEIGHT];
+
+            if *pixels & mask != 0 {
+                collisions += 1;
+            }
+
+            if mask != 0 {
+                *pixels ^= mask;
+                changed |= 1 << (row % Self::HEIGHT);
+            }
+        }
+
+        self.mark_changed(changed);
+        collisions
     }
 
     pub fn pixel_rows(&self) -> &[u64; 32] {
@@ -19,7 +79,81 @@ impl Display {
     }
 
     pub fn clear(&mut self) {
+        let changed = self.lit_rows();
         self.pixel_rows = [0; 32];
+        self.mark_changed(changed);
+    }
+
+    pub fn copy_from(&mut self, other: &Display) {
+        let changed = (0..Self::HEIGHT)
+            .filter(|&y| self.pixel_rows[y] != other.pixel_rows[y])
+            .fold(0, |mask, y| mask | 1 << y);
+
+        self.pixel_rows = other.pixel_rows;
+        self.mark_changed(changed);
+    }
+
+    /// Incremented every time the contents of the display change
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty != 0
+    }
+
+    /// Ranges of consecutive rows that changed since the last call to `clear_dirty`
+    pub fn dirty_spans(&self) -> DirtySpans {
+        DirtySpans { dirty: self.dirty, row: 0 }
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = 0;
+    }
+
+    /// Marks every row as dirty, e.g. after the frontend changes how pixels are colored
+    pub fn invalidate(&mut self) {
+        self.dirty = u32::MAX;
+    }
+
+    fn lit_rows(&self) -> u32 {
+        (0..Self::HEIGHT).filter(|&y| self.pixel_rows[y] != 0).fold(0, |mask, y| mask | 1 << y)
+    }
+
+    fn mark_changed(&mut self, rows: u32) {
+        if rows != 0 {
+            self.dirty |= rows;
+            self.version += 1;
+        }
+    }
+}
+
+pub struct DirtySpans {
+    dirty: u32,
+    row: usize,
+}
+
+impl Iterator for DirtySpans {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = self.dirty.checked_shr(self.row as u32).unwrap_or(0);
+
+        if remaining == 0 {
+            return None;
+        }
+
+        let start = self.row + remaining.trailing_zeros() as usize;
+        let end = start + (remaining >> (start - self.row)).trailing_ones() as usize;
+        self.row = end;
+
+        Some(start..end)
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -37,6 +171,138 @@ mod tests {
         assert_eq!(display.pixel_rows[23], 1 << 47);
     }
 
+    #[test]
+    fn test_draw_sprite() {
+        let mut display = Display::new();
+        assert_eq!(display.draw_sprite(8, 4, &[0xF0, 0x90], EdgeMode::Clip), 0);
+        assert_eq!(display.pixel_rows[4], 0xF0 << 48);
+        assert_eq!(display.pixel_rows[5], 0x90 << 48);
+
+        assert_eq!(display.draw_sprite(8, 4, &[0x80, 0x00, 0x80], EdgeMode::Clip), 1);
+        assert_eq!(display.pixel_rows[4], 0x70 << 48);
+        assert_eq!(display.pixel_rows[6], 0x80 << 48);
+    }
+
+    #[test]
+    fn test_draw_sprite_right_edge() {
+        let mut display = Display::new();
+        display.draw_sprite(60, 0, &[0xFF], EdgeMode::Clip);
+        display.draw_sprite(63, 1, &[0xFF], EdgeMode::Clip);
+        display.draw_sprite(60, 2, &[0xFF], EdgeMode::Wrap);
+        display.draw_sprite(63, 3, &[0xFF], EdgeMode::Wrap);
+
+        assert_eq!(display.pixel_rows[0], 0xF);
+        assert_eq!(display.pixel_rows[1], 0x1);
+        assert_eq!(display.pixel_rows[2], 0xF000_0000_0000_000F);
+        assert_eq!(display.pixel_rows[3], 0xFE00_0000_0000_0001);
+    }
+
+    #[test]
+    fn test_draw_sprite_bottom_edge() {
+        let mut display = Display::new();
+        display.draw_sprite(0, 30, &[0x80, 0x40, 0x20, 0x10], EdgeMode::Clip);
+
+        assert_eq!(display.pixel_rows[30], 1 << 63);
+        assert_eq!(display.pixel_rows[31], 1 << 62);
+        assert_eq!(display.pixel_rows[0], 0);
+
+        display.clear();
+        display.draw_sprite(0, 30, &[0x80, 0x40, 0x20, 0x10], EdgeMode::Wrap);
+
+        assert_eq!(display.pixel_rows[0], 1 << 61);
+        assert_eq!(display.pixel_rows[1], 1 << 60);
+    }
+
+    #[test]
+    fn test_draw_sprite_corner() {
+        let mut display = Display::new();
+        display.draw_sprite(62, 31, &[0xC0, 0xC0], EdgeMode::Wrap);
+        assert_eq!(display.pixel_rows[31], 0x3);
+        assert_eq!(display.pixel_rows[0], 0x3);
+
+        display.clear();
+        display.draw_sprite(62, 31, &[0xF0, 0xF0], EdgeMode::Wrap);
+        assert_eq!(display.pixel_rows[31], 0xC000_0000_0000_0003);
+        assert_eq!(display.pixel_rows[0], 0xC000_0000_0000_0003);
+
+        display.clear();
+        display.draw_sprite(62, 31, &[0xF0, 0xF0], EdgeMode::Clip);
+        assert_eq!(display.pixel_rows[31], 0x3);
+        assert_eq!(display.pixel_rows[0], 0);
+    }
+
+    #[test]
+    fn test_draw_sprite_start_wraps() {
+        let mut display = Display::new();
+        display.draw_sprite(64 + 1, 32 + 2, &[0x80], EdgeMode::Clip);
+        assert_eq!(display.pixel_rows[2], 1 << 62);
+    }
+
+    #[test]
+    fn test_draw_sprite_collision_rows() {
+        let mut display = Display::new();
+        display.draw_sprite(0, 0, &[0xFF; 4], EdgeMode::Clip);
+        assert_eq!(display.draw_sprite(4, 1, &[0x0F, 0xF0, 0xF0], EdgeMode::Clip), 2);
+        assert_eq!(display.draw_sprite(0, 31, &[0xFF, 0xFF], EdgeMode::Wrap), 1);
+    }
+
+    #[test]
+    fn test_dirty_spans() {
+        let mut display = Display::new();
+        assert_eq!(display.dirty_spans().next(), Some(0..32));
+
+        display.clear_dirty();
+        assert!(!display.is_dirty());
+        assert_eq!(display.dirty_spans().next(), None);
+
+        display.draw_sprite(0, 3, &[0x80, 0x80], EdgeMode::Clip);
+        display.draw_sprite(0, 10, &[0x80], EdgeMode::Clip);
+        display.draw_sprite(0, 31, &[0x80, 0x80], EdgeMode::Wrap);
+        assert_eq!(display.dirty_spans().collect::<Vec<_>>(), [0..1, 3..5, 10..11, 31..32]);
+    }
+
+    #[test]
+    fn test_dirty_empty_rows() {
+        let mut display = Display::new();
+        display.clear_dirty();
+
+        display.draw_sprite(0, 4, &[0x00, 0x80, 0x00], EdgeMode::Clip);
+        display.draw_sprite(60, 8, &[0x08], EdgeMode::Clip);
+        assert_eq!(display.dirty_spans().collect::<Vec<_>>(), vec![5..6]);
+    }
+
+    #[test]
+    fn test_version() {
+        let mut display = Display::new();
+        let version = display.version();
+
+        display.clear();
+        display.draw_sprite(0, 0, &[0x00], EdgeMode::Clip);
+        assert_eq!(display.version(), version);
+
+        display.draw_sprite(0, 0, &[0xFF], EdgeMode::Clip);
+        display.toggle(4, 4);
+        assert_eq!(display.version(), version + 2);
+
+        display.clear_dirty();
+        display.clear();
+        assert_eq!(display.version(), version + 3);
+        assert_eq!(display.dirty_spans().collect::<Vec<_>>(), [0..1, 4..5]);
+    }
+
+    #[test]
+    fn test_copy_from() {
+        let mut display = Display::new();
+        let mut other = Display::new();
+        other.draw_sprite(0, 7, &[0xFF], EdgeMode::Clip);
+        display.clear_dirty();
+
+        display.copy_from(&other);
+        assert_eq!(display.pixel_rows, other.pixel_rows);
+        assert_eq!(display.dirty_spans().collect::<Vec<_>>(), vec![7..8]);
+        assert_eq!(display.version(), 1);
+    }
+
     #[test]
     fn test_clear() {
         let mut display = Display::new();
@@ -1,10 +1,16 @@
 use crate::{Result, Display};
+use crate::display::EdgeMode;
+use crate::hash::Fnv1a;
 use crate::instruction::Instruction;
+use crate::quirks::Quirks;
+use crate::random::{RandomSource, XorShift};
 use crate::stack::Stack;
 
 pub const PROGRAM_OFFSET: usize = 0x200;
 pub const FONT_OFFSET: usize = 0x50;
 
+const MEMORY_SIZE: usize = 0x1000;
+
 pub const FONT: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -24,7 +30,7 @@ pub const FONT: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-pub struct Emulator<R: FnMut() -> u8> {
+pub struct Emulator<R: RandomSource> {
     pub memory: [u8; 0x1000],
     pub display: Display,
     pc: usize,
@@ -34,17 +40,57 @@ pub struct Emulator<R: FnMut() -> u8> {
     dt: u8,
     st: u8,
     v: [u8; 16],
-    rng_func: R,
+    rng: R,
+    quirks: Quirks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+}
+
+#[derive(Clone)]
+pub struct SaveState<R: RandomSource + Clone> {
+    memory: [u8; 0x1000],
+    display: Display,
+    pc: usize,
+    i: usize,
+    stack: Stack<16>,
+    dt: u8,
+    st: u8,
+    v: [u8; 16],
+    rng: R,
+}
+
+impl<R: RandomSource + Clone> SaveState<R> {
+    pub fn rng(&self) -> &R {
+        &self.rng
+    }
 }
 
-impl Emulator<fn() -> u8> {
+impl Emulator<XorShift> {
+    /// An emulator for callers that don't care which numbers `RND` draws. It's backed by xorshift with a fixed seed,
+    /// so `RND` still varies but every run draws the same sequence.
     pub fn except_rng() -> Self {
-        Self::new(|| 0)
+        Self::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self::new(XorShift::new(seed))
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
     }
 }
 
-impl<R: FnMut() -> u8> Emulator<R> {
-    pub fn new(rng_func: R) -> Self {
+impl<R: RandomSource> Emulator<R> {
+    pub fn new(rng: R) -> Self {
         Self {
             memory: [0; 0x1000],
             display: Display::new(),
@@ -54,11 +100,28 @@ impl<R: FnMut() -> u8> Emulator<R> {
             stack: Stack::new(),
             dt: 0,
             st: 0,
-            rng_func,
+            rng,
             keys: 0,
+            quirks: Quirks::default(),
         }
     }
 
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn rng(&self) -> &R {
+        &self.rng
+    }
+
+    pub fn rng_mut(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
     pub fn load_program(&mut self, program: &[u8]) {
         for (i, val) in program.iter().enumerate() {
             self.memory[PROGRAM_OFFSET + i] = *val;
@@ -91,7 +154,7 @@ impl<R: FnMut() -> u8> Emulator<R> {
     pub fn cycle(&mut self, times: u32) -> Result<()> {
         for _ in 0..times {
             let opcode = self.fetch();
-            self.pc += 2;
+            self.pc = (self.pc + 2) % MEMORY_SIZE;
 
             let instruction = Instruction::try_from(opcode)?;
             self.execute(instruction)?;
@@ -108,13 +171,53 @@ impl<R: FnMut() -> u8> Emulator<R> {
         self.keys &= !(1 << key);
     }
 
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i as u16,
+            pc: self.pc as u16,
+            sp: self.stack.len() as u8,
+            dt: self.dt,
+            st: self.st,
+        }
+    }
+
+    /// Sets every register except the stack pointer, which can only change through `CALL` and `RET`
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.v = registers.v;
+        self.i = registers.i as usize & 0xFFF;
+        self.pc = registers.pc as usize & 0xFFF;
+        self.dt = registers.dt;
+        self.st = registers.st;
+    }
+
+    /// Return addresses of the active subroutine calls, from the outermost call
+    pub fn call_stack(&self) -> &[usize] {
+        self.stack.as_slice()
+    }
+
     pub fn sound_timer(&self) -> u8 {
         self.st
     }
 
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+
+        for row in self.display.pixel_rows() {
+            hasher.write(&row.to_be_bytes());
+        }
+
+        hasher.write(&self.v);
+        hasher.write(&(self.pc as u16).to_be_bytes());
+        hasher.write(&(self.i as u16).to_be_bytes());
+        hasher.write(&[self.dt, self.st]);
+        hasher.finish()
+    }
+
+    // Addresses past the end of memory wrap around, like the 12-bit address bus of the VIP
     fn fetch(&self) -> u16 {
-        let hi = self.memory[self.pc] as u16;
-        let lo = self.memory[self.pc + 1] as u16;
+        let hi = self.memory[self.pc % MEMORY_SIZE] as u16;
+        let lo = self.memory[(self.pc + 1) % MEMORY_SIZE] as u16;
         (hi << 8) | lo
     }
 
@@ -146,29 +249,43 @@ impl<R: FnMut() -> u8> Emulator<R> {
             Instruction::LDVxKK(x, kk) => self.v[x] = kk,
             Instruction::ADDVxKK(x, kk) => self.v[x] = self.v[x].wrapping_add(kk),
             Instruction::LDVxVy(x, y) => self.v[x] = self.v[y],
-            Instruction::OR(x, y) => self.v[x] |= self.v[y],
-            Instruction::AND(x, y) => self.v[x] &= self.v[y],
-            Instruction::XOR(x, y) => self.v[x] ^= self.v[y],
+            Instruction::OR(x, y) => {
+                self.v[x] |= self.v[y];
+                self.reset_vf();
+            }
+            Instruction::AND(x, y) => {
+                self.v[x] &= self.v[y];
+                self.reset_vf();
+            }
+            Instruction::XOR(x, y) => {
+                self.v[x] ^= self.v[y];
+                self.reset_vf();
+            }
+            // The arithmetic instructions write VF after the result, so the flag wins when VX is VF
             Instruction::ADD(x, y) => {
                 let (sum, overflow) = self.v[x].overflowing_add(self.v[y]);
-                self.v[0xF] = overflow.into();
                 self.v[x] = sum;
+                self.v[0xF] = overflow.into();
             }
             Instruction::SUB(x, y) => {
-                self.v[0xF] = (self.v[x] >= self.v[y]).into();
+                let flag = self.v[x] >= self.v[y];
                 self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+                self.v[0xF] = flag.into();
             }
-            Instruction::SHR(x, _) => {
-                self.v[0xF] = self.v[x] & 1;
-                self.v[x] >>= 1;
+            Instruction::SHR(x, y) => {
+                let value = if self.quirks.shift_vy { self.v[y] } else { self.v[x] };
+                self.v[x] = value >> 1;
+                self.v[0xF] = value & 1;
             }
             Instruction::SUBN(x, y) => {
-                self.v[0xF] = (self.v[y] >= self.v[x]).into();
+                let flag = self.v[y] >= self.v[x];
                 self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                self.v[0xF] = flag.into();
             }
-            Instruction::SHL(x, _) => {
-                self.v[0xF] = (self.v[x] & 0b1000_0000) >> 7;
-                self.v[x] <<= 1;
+            Instruction::SHL(x, y) => {
+                let value = if self.quirks.shift_vy { self.v[y] } else { self.v[x] };
+                self.v[x] = value << 1;
+                self.v[0xF] = (value & 0b1000_0000) >> 7;
             }
             Instruction::SNE(x, y) => {
                 if self.v[x] != self.v[y] {
@@ -176,29 +293,22 @@ impl<R: FnMut() -> u8> Emulator<R> {
                 }
             }
             Instruction::LDI(addr) => self.i = addr,
-            Instruction::JPV0(addr) => self.pc = addr + self.v[0] as usize,
-            Instruction::RND(x, kk) => self.v[x] = (self.rng_func)() & kk,
+            Instruction::JPV0(addr) => {
+                let offset = if self.quirks.jump_vx { self.v[(addr >> 8) & 0xF] } else { self.v[0] };
+                self.pc = addr + offset as usize;
+            }
+            Instruction::RND(x, kk) => self.v[x] = self.rng.next_byte() & kk,
             Instruction::DRW(x, y, len) => {
-                let pos_x = self.v[x] % 64;
-                let pos_y = self.v[y] % 32;
-
-                let bytes = &self.memory[self.i..(self.i + len as usize)];
-
-                self.v[0xF] = 0;
-
-                for (i, byte) in bytes.iter().enumerate() {
-                    let y = pos_y + i as u8;
+                // Sprites that run past the end of memory continue from address 0
+                let mut bytes = [0; 15];
+                for (row, byte) in bytes[..len as usize].iter_mut().enumerate() {
+                    *byte = self.memory[(self.i + row) % MEMORY_SIZE];
+                }
 
-                    for bit in 0..8 {
-                        let x = pos_x + bit;
-                        let mask: u8 = 0b1000_0000 >> bit;
+                let mode = if self.quirks.clip { EdgeMode::Clip } else { EdgeMode::Wrap };
+                let collisions = self.display.draw_sprite(self.v[x] as usize, self.v[y] as usize, &bytes[..len as usize], mode);
 
-                        if byte & mask != 0 {
-                            let result = self.display.toggle(x, y);
-                            self.v[0xF] |= !result as u8;
-                        }
-                    }
-                }
+                self.v[0xF] = (collisions > 0) as u8;
             }
             Instruction::SKP(x) => {
                 if self.keys & (1 << self.v[x]) != 0 {
@@ -231,19 +341,28 @@ impl<R: FnMut() -> u8> Emulator<R> {
                 let char = (self.v[x] & 0xF) as usize;
                 self.i = FONT_OFFSET + char * 5;
             }
+            #[allow(clippy::identity_op)]
             Instruction::LDBVx(x) => {
-                self.memory[self.i + 0] = self.v[x] / 100;
-                self.memory[self.i + 1] = (self.v[x] / 10) % 10;
-                self.memory[self.i + 2] = self.v[x] % 10;
+                self.memory[(self.i + 0) % MEMORY_SIZE] = self.v[x] / 100;
+                self.memory[(self.i + 1) % MEMORY_SIZE] = (self.v[x] / 10) % 10;
+                self.memory[(self.i + 2) % MEMORY_SIZE] = self.v[x] % 10;
             }
             Instruction::LDIVx(x) => {
                 for i in 0..=x {
-                    self.memory[self.i + i] = self.v[i];
+                    self.memory[(self.i + i) % MEMORY_SIZE] = self.v[i];
+                }
+
+                if self.quirks.load_store_i {
+                    self.i += x + 1;
                 }
             }
             Instruction::LDVxI(x) => {
                 for i in 0..=x {
-                    self.v[i] = self.memory[self.i + i];
+                    self.v[i] = self.memory[(self.i + i) % MEMORY_SIZE];
+                }
+
+                if self.quirks.load_store_i {
+                    self.i += x + 1;
                 }
             }
         };
@@ -251,6 +370,12 @@ impl<R: FnMut() -> u8> Emulator<R> {
         Ok(())
     }
 
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+    }
+
     fn load_font(&mut self) {
         for (i, val) in FONT.iter().enumerate() {
             self.memory[FONT_OFFSET + i] = *val;
@@ -258,6 +383,34 @@ impl<R: FnMut() -> u8> Emulator<R> {
     }
 }
 
+impl<R: RandomSource + Clone> Emulator<R> {
+    pub fn save_state(&self) -> SaveState<R> {
+        SaveState {
+            memory: self.memory,
+            display: self.display.clone(),
+            pc: self.pc,
+            i: self.i,
+            stack: self.stack.clone(),
+            dt: self.dt,
+            st: self.st,
+            v: self.v,
+            rng: self.rng.clone(),
+        }
+    }
+
+    pub fn load_state(&mut self, state: &SaveState<R>) {
+        self.memory = state.memory;
+        self.display.copy_from(&state.display);
+        self.pc = state.pc;
+        self.i = state.i;
+        self.stack = state.stack.clone();
+        self.dt = state.dt;
+        self.st = state.st;
+        self.v = state.v;
+        self.rng = state.rng.clone();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -299,6 +452,7 @@ mod tests {
         emulator.execute(Instruction::CALL(0x350))?;
 
         assert_eq!(emulator.pc, 0x350);
+        assert_eq!(emulator.call_stack(), [0x240]);
         assert_eq!(emulator.stack.pop()?, 0x240);
         Ok(())
     }
@@ -421,4 +575,223 @@ mod tests {
         assert_eq!(emulator.v[0xB], 0b0010_1010);
         Ok(())
     }
+
+    #[test]
+    fn test_flag_overrides_vf_result() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.v[0xF] = 0x80;
+        emulator.v[0x1] = 0x80;
+        emulator.execute(Instruction::ADD(0xF, 0x1))?;
+        assert_eq!(emulator.v[0xF], 1);
+
+        emulator.v[0xF] = 0x10;
+        emulator.v[0x1] = 0x20;
+        emulator.execute(Instruction::SUB(0xF, 0x1))?;
+        assert_eq!(emulator.v[0xF], 0);
+
+        emulator.v[0xF] = 0x02;
+        emulator.execute(Instruction::SHR(0xF, 0xF))?;
+        assert_eq!(emulator.v[0xF], 0);
+
+        emulator.v[0xF] = 0x81;
+        emulator.execute(Instruction::SHL(0xF, 0xF))?;
+        assert_eq!(emulator.v[0xF], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vf_reset_quirk() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.v[0xF] = 1;
+        emulator.execute(Instruction::XOR(0x0, 0x1))?;
+        assert_eq!(emulator.v[0xF], 1);
+
+        emulator.set_quirks(Quirks::VIP);
+        emulator.execute(Instruction::XOR(0x0, 0x1))?;
+        assert_eq!(emulator.v[0xF], 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_quirk() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.v[0x1] = 0b0000_0110;
+        emulator.v[0x2] = 0b0000_0011;
+        emulator.execute(Instruction::SHR(0x1, 0x2))?;
+        assert_eq!((emulator.v[0x1], emulator.v[0xF]), (0b0000_0011, 0));
+
+        emulator.set_quirks(Quirks::VIP);
+        emulator.execute(Instruction::SHR(0x1, 0x2))?;
+        assert_eq!((emulator.v[0x1], emulator.v[0xF]), (0b0000_0001, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_jump_quirk() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.v[0x0] = 0x10;
+        emulator.v[0x3] = 0x20;
+        emulator.execute(Instruction::JPV0(0x300))?;
+        assert_eq!(emulator.pc, 0x310);
+
+        emulator.set_quirks(Quirks::SCHIP);
+        emulator.execute(Instruction::JPV0(0x300))?;
+        assert_eq!(emulator.pc, 0x320);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_store_quirk() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.i = 0x300;
+        emulator.execute(Instruction::LDIVx(0x3))?;
+        assert_eq!(emulator.i, 0x300);
+
+        emulator.set_quirks(Quirks::VIP);
+        emulator.execute(Instruction::LDVxI(0x3))?;
+        assert_eq!(emulator.i, 0x304);
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_wraps() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.memory[0xFFF] = 0x60;
+        emulator.memory[0x000] = 0x2A;
+        emulator.pc = 0xFFF;
+        emulator.cycle(1)?;
+        assert_eq!((emulator.v[0x0], emulator.pc), (0x2A, 0x001));
+
+        emulator.i = 0xFFE;
+        emulator.execute(Instruction::LDIVx(0x3))?;
+        emulator.execute(Instruction::LDBVx(0x0))?;
+        assert_eq!(emulator.memory[0xFFE..], [0, 4]);
+        assert_eq!(emulator.memory[..2], [2, 0]);
+
+        emulator.execute(Instruction::LDVxI(0x3))?;
+        assert_eq!(emulator.v[..4], [0, 4, 2, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_drw_clip_quirk() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.memory[0x300] = 0xFF;
+        emulator.i = 0x300;
+        emulator.v[0x0] = 60;
+        emulator.v[0x1] = 31;
+        emulator.execute(Instruction::DRW(0x0, 0x1, 1))?;
+        assert_eq!(emulator.display.pixel_rows()[31], 0xF);
+
+        emulator.display.clear();
+        emulator.set_quirks(Quirks { clip: false, ..Quirks::default() });
+        emulator.execute(Instruction::DRW(0x0, 0x1, 1))?;
+        assert_eq!(emulator.display.pixel_rows()[31], 0xF000_0000_0000_000F);
+        Ok(())
+    }
+
+    #[test]
+    fn test_drw_wraps_memory() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.memory[0xFFE] = 0x80;
+        emulator.memory[0xFFF] = 0x40;
+        emulator.memory[0x000] = 0x20;
+        emulator.i = 0xFFE;
+        emulator.execute(Instruction::DRW(0x0, 0x1, 5))?;
+
+        let rows = emulator.display.pixel_rows();
+        assert_eq!(rows[..4], [1 << 63, 1 << 62, 1 << 61, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_registers() -> Result<()> {
+        let mut emulator = Emulator::except_rng();
+        emulator.execute(Instruction::CALL(0x300))?;
+        emulator.execute(Instruction::LDVxKK(0x3, 0x42))?;
+
+        let mut registers = emulator.registers();
+        assert_eq!((registers.pc, registers.sp, registers.v[0x3]), (0x300, 1, 0x42));
+
+        registers.i = 0x1234;
+        registers.sp = 5;
+        registers.dt = 9;
+        emulator.set_registers(registers);
+        assert_eq!(emulator.registers(), Registers { i: 0x234, sp: 1, ..registers });
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum() -> Result<()> {
+        let mut a = Emulator::except_rng();
+        let mut b = Emulator::except_rng();
+        assert_eq!(a.checksum(), b.checksum());
+
+        a.execute(Instruction::LDVxKK(0x3, 0x10))?;
+        assert_ne!(a.checksum(), b.checksum());
+
+        b.execute(Instruction::LDVxKK(0x3, 0x10))?;
+        b.display.toggle(5, 5);
+        assert_ne!(a.checksum(), b.checksum());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rnd_seeded() -> Result<()> {
+        let mut a = Emulator::with_seed(0x5EED);
+        let mut b = Emulator::with_seed(0x5EED);
+
+        for _ in 0..32 {
+            a.execute(Instruction::RND(0x0, 0xFF))?;
+            b.execute(Instruction::RND(0x0, 0xFF))?;
+            assert_eq!(a.v[0x0], b.v[0x0]);
+        }
+
+        assert_eq!(a.seed(), 0x5EED);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rnd_except_rng() -> Result<()> {
+        let mut a = Emulator::except_rng();
+        let mut b = Emulator::except_rng();
+        let mut values = Vec::new();
+
+        for _ in 0..8 {
+            a.execute(Instruction::RND(0x0, 0xFF))?;
+            b.execute(Instruction::RND(0x0, 0xFF))?;
+            assert_eq!(a.v[0x0], b.v[0x0]);
+            values.push(a.v[0x0]);
+        }
+
+        assert!(values.iter().any(|value| *value != values[0]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rnd_closure() -> Result<()> {
+        let mut emulator = Emulator::new(|| 0b1011_0110);
+        emulator.execute(Instruction::RND(0x4, 0x0F))?;
+
+        assert_eq!(emulator.v[0x4], 0b0000_0110);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_state() -> Result<()> {
+        let mut emulator = Emulator::with_seed(77);
+        emulator.execute(Instruction::LDVxKK(0x1, 0x20))?;
+        let state = emulator.save_state();
+
+        emulator.execute(Instruction::RND(0x2, 0xFF))?;
+        let expected = emulator.v[0x2];
+        emulator.execute(Instruction::LDVxKK(0x1, 0x30))?;
+
+        emulator.load_state(&state);
+        assert_eq!(emulator.v[0x1], 0x20);
+
+        emulator.execute(Instruction::RND(0x2, 0xFF))?;
+        assert_eq!(emulator.v[0x2], expected);
+        Ok(())
+    }
 }
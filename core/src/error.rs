@@ -7,6 +7,13 @@ pub enum Error {
     IllegalOpcode {  opcode: u16 },
     StackOverflow,
     StackUnderflow,
+    InvalidMovie { line: usize },
+    InvalidCheats { line: usize },
+    InvalidCoverage { line: usize },
+    CoverageMismatch,
+    Desync { frame: u64 },
+    UnknownPlatform { name: String },
+    UnknownQuirk { name: String },
 }
 
 impl Display for Error {
@@ -15,6 +22,13 @@ impl Display for Error {
             Self::IllegalOpcode { opcode } => write!(f, "illegal opcode: {}", opcode),
             Self::StackOverflow => write!(f, "stack overflow"),
             Self::StackUnderflow => write!(f, "stack underflow"),
+            Self::InvalidMovie { line } => write!(f, "invalid movie file at line {}", line),
+            Self::InvalidCheats { line } => write!(f, "invalid cheat file at line {}", line),
+            Self::InvalidCoverage { line } => write!(f, "invalid coverage file at line {}", line),
+            Self::CoverageMismatch => write!(f, "coverage was recorded with a different ROM"),
+            Self::Desync { frame } => write!(f, "movie desynced at frame {}", frame),
+            Self::UnknownPlatform { name } => write!(f, "unknown platform: {}", name),
+            Self::UnknownQuirk { name } => write!(f, "unknown quirk: {}", name),
         }
     }
 }
@@ -0,0 +1,189 @@
+use std::ops::Range;
+
+use crate::Display;
+
+pub const PACKED_SIZE: usize = Display::WIDTH * Display::HEIGHT / 8;
+
+impl Display {
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        assert!(x < Self::WIDTH && y < Self::HEIGHT, "pixel ({}, {}) is outside the {}x{} display", x, y, Self::WIDTH, Self::HEIGHT);
+        self.pixel_rows()[y] & (1 << (63 - x)) != 0
+    }
+
+    /// Iterates over every pixel as `(x, y, on)`, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, bool)> + '_ {
+        (0..Self::HEIGHT).flat_map(move |y| (0..Self::WIDTH).map(move |x| (x, y, self.pixel(x, y))))
+    }
+
+    /// Packs the display into one bit per pixel, row by row with the leftmost pixel in the highest bit
+    pub fn to_1bpp(&self) -> [u8; PACKED_SIZE] {
+        let mut packed = [0; PACKED_SIZE];
+
+        for (chunk, row) in packed.chunks_exact_mut(8).zip(self.pixel_rows()) {
+            chunk.copy_from_slice(&row.to_be_bytes());
+        }
+
+        packed
+    }
+
+    /// Renders into a buffer of `3 * scale * scale` bytes per pixel using `[off, on]` colors. `scale` must be at least 1.
+    pub fn render_rgb24(&self, out: &mut [u8], colors: [[u8; 3]; 2], scale: usize) {
+        self.render(out, colors, scale, 0..Self::HEIGHT);
+    }
+
+    /// Like `render_rgb24`, for the display rows in `rows` into a buffer that holds only those rows
+    pub fn render_rows_rgb24(&self, out: &mut [u8], colors: [[u8; 3]; 2], scale: usize, rows: Range<usize>) {
+        self.render(out, colors, scale, rows);
+    }
+
+    pub fn render_rgba8(&self, out: &mut [u8], colors: [[u8; 4]; 2], scale: usize) {
+        self.render(out, colors, scale, 0..Self::HEIGHT);
+    }
+
+    pub fn render_rgb565(&self, out: &mut [u16], colors: [u16; 2], scale: usize) {
+        self.render(out, colors.map(|color| [color]), scale, 0..Self::HEIGHT);
+    }
+
+    fn render<T: Copy, const N: usize>(&self, out: &mut [T], colors: [[T; N]; 2], scale: usize, rows: Range<usize>) {
+        assert!(scale > 0, "cannot render the display at scale 0");
+        let stride = Self::WIDTH * scale * N;
+        assert_eq!(out.len(), stride * rows.len() * scale);
+
+        for (y, lines) in rows.zip(out.chunks_exact_mut(stride * scale)) {
+            let (line, rest) = lines.split_at_mut(stride);
+
+            for (x, pixel) in line.chunks_exact_mut(scale * N).enumerate() {
+                let color = colors[self.pixel(x, y) as usize];
+
+                for dot in pixel.chunks_exact_mut(N) {
+                    dot.copy_from_slice(&color);
+                }
+            }
+
+            for copy in rest.chunks_exact_mut(stride) {
+                copy.copy_from_slice(line);
+            }
+        }
+    }
+}
+
+pub fn rgb565(r: u8, g: u8, b: u8) -> u16 {
+    (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const OFF: [u8; 4] = [1, 2, 3, 255];
+    const ON: [u8; 4] = [200, 150, 100, 255];
+
+    fn toggled(points: &[(u8, u8)]) -> (Display, Vec<bool>) {
+        let mut display = Display::new();
+        let mut expected = vec![false; Display::WIDTH * Display::HEIGHT];
+
+        for &(x, y) in points {
+            display.toggle(x, y);
+            let i = (y as usize % Display::HEIGHT) * Display::WIDTH + x as usize % Display::WIDTH;
+            expected[i] = !expected[i];
+        }
+
+        (display, expected)
+    }
+
+    #[test]
+    fn test_rgb565() {
+        assert_eq!(rgb565(255, 255, 255), 0xFFFF);
+        assert_eq!(rgb565(255, 0, 0), 0xF800);
+        assert_eq!(rgb565(0, 255, 0), 0x07E0);
+        assert_eq!(rgb565(0, 0, 255), 0x001F);
+    }
+
+    #[test]
+    fn test_to_1bpp() {
+        let mut display = Display::new();
+        display.toggle(0, 0);
+        display.toggle(9, 0);
+        display.toggle(63, 31);
+
+        let packed = display.to_1bpp();
+        assert_eq!(packed[0], 0x80);
+        assert_eq!(packed[1], 0x40);
+        assert_eq!(packed[PACKED_SIZE - 1], 0x01);
+        assert_eq!(packed.iter().filter(|byte| **byte != 0).count(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel (64, 0) is outside the 64x32 display")]
+    fn test_pixel_out_of_bounds() {
+        Display::new().pixel(64, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot render the display at scale 0")]
+    fn test_render_scale_zero() {
+        Display::new().render_rgb24(&mut [], [[0; 3]; 2], 0);
+    }
+
+    #[test]
+    fn test_render_rows() {
+        let mut display = Display::new();
+        display.toggle(1, 3);
+
+        let mut full = vec![0; Display::WIDTH * Display::HEIGHT * 2 * 2 * 3];
+        let mut rows = vec![0; Display::WIDTH * 2 * 2 * 2 * 3];
+        display.render_rgb24(&mut full, [[0; 3], [255; 3]], 2);
+        display.render_rows_rgb24(&mut rows, [[0; 3], [255; 3]], 2, 2..4);
+
+        let stride = Display::WIDTH * 2 * 3;
+        assert_eq!(rows, full[2 * 2 * stride..4 * 2 * stride]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_pixels(points in prop::collection::vec(any::<(u8, u8)>(), 0..200)) {
+            let (display, expected) = toggled(&points);
+
+            for (x, y, on) in display.pixels() {
+                prop_assert_eq!(on, expected[y * Display::WIDTH + x]);
+            }
+
+            prop_assert_eq!(display.pixels().count(), expected.len());
+        }
+
+        #[test]
+        fn test_packed(points in prop::collection::vec(any::<(u8, u8)>(), 0..200)) {
+            let (display, expected) = toggled(&points);
+            let packed = display.to_1bpp();
+
+            for (i, on) in expected.iter().enumerate() {
+                prop_assert_eq!(packed[i / 8] & (0x80 >> (i % 8)) != 0, *on);
+            }
+        }
+
+        #[test]
+        fn test_render(points in prop::collection::vec(any::<(u8, u8)>(), 0..200), scale in 1usize..4) {
+            let (display, expected) = toggled(&points);
+            let width = Display::WIDTH * scale;
+            let pixels = width * Display::HEIGHT * scale;
+
+            let mut rgba = vec![0; pixels * 4];
+            let mut rgb = vec![0; pixels * 3];
+            let mut rgb565 = vec![0; pixels];
+
+            display.render_rgba8(&mut rgba, [OFF, ON], scale);
+            display.render_rgb24(&mut rgb, [[1, 2, 3], [200, 150, 100]], scale);
+            display.render_rgb565(&mut rgb565, [0x0000, 0xFFFF], scale);
+
+            for i in 0..pixels {
+                let on = expected[(i / width / scale) * Display::WIDTH + (i % width) / scale];
+                let color = if on { ON } else { OFF };
+
+                prop_assert_eq!(&rgba[i * 4..i * 4 + 4], &color);
+                prop_assert_eq!(&rgb[i * 3..i * 3 + 3], &color[..3]);
+                prop_assert_eq!(rgb565[i], if on { 0xFFFF } else { 0x0000 });
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+pub struct Fnv1a {
+    state: u64,
+}
+
+impl Fnv1a {
+    pub fn new() -> Self {
+        Self { state: FNV_OFFSET }
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn rom_hash(program: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(program);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(rom_hash(&[]), FNV_OFFSET);
+    }
+
+    #[test]
+    fn test_known_value() {
+        assert_eq!(rom_hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut hasher = Fnv1a::new();
+        hasher.write(&[0x00, 0xE0]);
+        hasher.write(&[0x12, 0x00]);
+
+        assert_eq!(hasher.finish(), rom_hash(&[0x00, 0xE0, 0x12, 0x00]));
+    }
+}
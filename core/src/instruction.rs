@@ -1,3 +1,5 @@
+use std::fmt::{Display, Formatter};
+
 use crate::Error;
 
 pub enum Instruction {
@@ -38,6 +40,91 @@ pub enum Instruction {
     LDVxI(usize),
 }
 
+impl Instruction {
+    /// Name of the instruction kind, without its operands
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SYS(..) => "SYS",
+            Self::CLS => "CLS",
+            Self::RET => "RET",
+            Self::JP(..) => "JP",
+            Self::CALL(..) => "CALL",
+            Self::SEVxKK(..) => "SEVxKK",
+            Self::SNEVxKK(..) => "SNEVxKK",
+            Self::SEVxVy(..) => "SEVxVy",
+            Self::LDVxKK(..) => "LDVxKK",
+            Self::ADDVxKK(..) => "ADDVxKK",
+            Self::LDVxVy(..) => "LDVxVy",
+            Self::OR(..) => "OR",
+            Self::AND(..) => "AND",
+            Self::XOR(..) => "XOR",
+            Self::ADD(..) => "ADD",
+            Self::SUB(..) => "SUB",
+            Self::SHR(..) => "SHR",
+            Self::SUBN(..) => "SUBN",
+            Self::SHL(..) => "SHL",
+            Self::SNE(..) => "SNE",
+            Self::LDI(..) => "LDI",
+            Self::JPV0(..) => "JPV0",
+            Self::RND(..) => "RND",
+            Self::DRW(..) => "DRW",
+            Self::SKP(..) => "SKP",
+            Self::SKNP(..) => "SKNP",
+            Self::LDVxDT(..) => "LDVxDT",
+            Self::LDVxK(..) => "LDVxK",
+            Self::LDDTVx(..) => "LDDTVx",
+            Self::LDSTVx(..) => "LDSTVx",
+            Self::ADDIVx(..) => "ADDIVx",
+            Self::LDFVx(..) => "LDFVx",
+            Self::LDBVx(..) => "LDBVx",
+            Self::LDIVx(..) => "LDIVx",
+            Self::LDVxI(..) => "LDVxI",
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::SYS(addr) => write!(f, "SYS 0x{:03X}", addr),
+            Self::CLS => write!(f, "CLS"),
+            Self::RET => write!(f, "RET"),
+            Self::JP(addr) => write!(f, "JP 0x{:03X}", addr),
+            Self::CALL(addr) => write!(f, "CALL 0x{:03X}", addr),
+            Self::SEVxKK(x, kk) => write!(f, "SE V{:X}, 0x{:02X}", x, kk),
+            Self::SNEVxKK(x, kk) => write!(f, "SNE V{:X}, 0x{:02X}", x, kk),
+            Self::SEVxVy(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Self::LDVxKK(x, kk) => write!(f, "LD V{:X}, 0x{:02X}", x, kk),
+            Self::ADDVxKK(x, kk) => write!(f, "ADD V{:X}, 0x{:02X}", x, kk),
+            Self::LDVxVy(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Self::OR(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Self::AND(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Self::XOR(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Self::ADD(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Self::SUB(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Self::SHR(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Self::SUBN(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Self::SHL(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Self::SNE(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Self::LDI(addr) => write!(f, "LD I, 0x{:03X}", addr),
+            Self::JPV0(addr) => write!(f, "JP V0, 0x{:03X}", addr),
+            Self::RND(x, kk) => write!(f, "RND V{:X}, 0x{:02X}", x, kk),
+            Self::DRW(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Self::SKP(x) => write!(f, "SKP V{:X}", x),
+            Self::SKNP(x) => write!(f, "SKNP V{:X}", x),
+            Self::LDVxDT(x) => write!(f, "LD V{:X}, DT", x),
+            Self::LDVxK(x) => write!(f, "LD V{:X}, K", x),
+            Self::LDDTVx(x) => write!(f, "LD DT, V{:X}", x),
+            Self::LDSTVx(x) => write!(f, "LD ST, V{:X}", x),
+            Self::ADDIVx(x) => write!(f, "ADD I, V{:X}", x),
+            Self::LDFVx(x) => write!(f, "LD F, V{:X}", x),
+            Self::LDBVx(x) => write!(f, "LD B, V{:X}", x),
+            Self::LDIVx(x) => write!(f, "LD [I], V{:X}", x),
+            Self::LDVxI(x) => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
+
 impl TryFrom<u16> for Instruction {
     type Error = Error;
 
@@ -99,4 +186,20 @@ impl TryFrom<u16> for Instruction {
             _ => return Err(Error::IllegalOpcode { opcode }),
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() -> Result<(), Error> {
+        let listing: Vec<_> = [0x00E0, 0x2208, 0x6A05, 0x8AB6, 0xA300, 0xD125, 0xF065, 0xF133]
+            .into_iter()
+            .map(|opcode| Instruction::try_from(opcode).map(|instruction| instruction.to_string()))
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(listing, ["CLS", "CALL 0x208", "LD VA, 0x05", "SHR VA, VB", "LD I, 0x300", "DRW V1, V2, 5", "LD V0, [I]", "LD B, V1"]);
+        Ok(())
+    }
+}
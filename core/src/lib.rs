@@ -1,11 +1,23 @@
 mod display;
+mod framebuffer;
 mod stack;
 mod instruction;
 mod emulator;
+mod hash;
+mod random;
+mod quirks;
+pub mod cheats;
+pub mod coverage;
+pub mod movie;
+pub mod profiler;
 pub mod error;
 
-pub use crate::emulator::Emulator;
-pub use crate::display::Display;
+pub use crate::emulator::{Emulator, Registers, SaveState};
+pub use crate::display::{DirtySpans, Display, EdgeMode};
+pub use crate::framebuffer::{rgb565, PACKED_SIZE};
 pub use crate::instruction::Instruction;
 pub use crate::error::Error;
 pub use crate::error::Result;
+pub use crate::hash::rom_hash;
+pub use crate::random::{RandomSource, XorShift};
+pub use crate::quirks::{Platform, Quirks};
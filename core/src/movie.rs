@@ -0,0 +1,367 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::{Emulator, Error, Quirks, RandomSource, Result};
+use crate::hash::rom_hash;
+
+pub const CHECKSUM_INTERVAL: u64 = 60;
+
+const MAGIC: &str = "chip8-movie 1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(u8),
+    KeyUp(u8),
+    Cycles(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub seed: u64,
+    pub rom_hash: u64,
+    /// The quirks and instructions per second the movie was recorded with, which the replay has to match
+    pub quirks: Quirks,
+    pub ips: u32,
+    pub frames: u64,
+    pub inputs: Vec<(u64, InputEvent)>,
+    pub checksums: Vec<(u64, u64)>,
+}
+
+impl Movie {
+    pub fn matches_program(&self, program: &[u8]) -> bool {
+        self.rom_hash == rom_hash(program)
+    }
+}
+
+impl Display for Movie {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", MAGIC)?;
+        writeln!(f, "seed {:016x}", self.seed)?;
+        writeln!(f, "rom {:016x}", self.rom_hash)?;
+        writeln!(f, "quirks {}", self.quirks)?;
+        writeln!(f, "ips {}", self.ips)?;
+        writeln!(f, "frames {}", self.frames)?;
+
+        for (frame, event) in &self.inputs {
+            match event {
+                InputEvent::KeyDown(key) => writeln!(f, "{} down {:x}", frame, key)?,
+                InputEvent::KeyUp(key) => writeln!(f, "{} up {:x}", frame, key)?,
+                InputEvent::Cycles(cycles) => writeln!(f, "{} cycles {}", frame, cycles)?,
+            }
+        }
+
+        for (frame, checksum) in &self.checksums {
+            writeln!(f, "{} checksum {:016x}", frame, checksum)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Movie {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+
+        match lines.next() {
+            Some((_, line)) if line.trim() == MAGIC => {}
+            other => return Err(Error::InvalidMovie { line: other.map_or(1, |(i, _)| i + 1) }),
+        }
+
+        let mut movie = Self {
+            seed: 0,
+            rom_hash: 0,
+            quirks: Quirks::default(),
+            ips: 0,
+            frames: 0,
+            inputs: Vec::new(),
+            checksums: Vec::new(),
+        };
+
+        for (i, line) in lines {
+            let invalid = || Error::InvalidMovie { line: i + 1 };
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            match parts.as_slice() {
+                ["seed", seed] => movie.seed = u64::from_str_radix(seed, 16).map_err(|_| invalid())?,
+                ["rom", hash] => movie.rom_hash = u64::from_str_radix(hash, 16).map_err(|_| invalid())?,
+                ["quirks", list] => movie.quirks.apply(list).map_err(|_| invalid())?,
+                ["ips", ips] => movie.ips = ips.parse().ok().filter(|ips| *ips > 0).ok_or_else(invalid)?,
+                ["frames", frames] => movie.frames = frames.parse().map_err(|_| invalid())?,
+                [frame, kind, value] => {
+                    let frame: u64 = frame.parse().map_err(|_| invalid())?;
+
+                    match *kind {
+                        "checksum" => {
+                            let checksum = u64::from_str_radix(value, 16).map_err(|_| invalid())?;
+                            movie.checksums.push((frame, checksum));
+                        }
+                        "down" | "up" => {
+                            let key = u8::from_str_radix(value, 16).ok().filter(|k| *k < 16).ok_or_else(invalid)?;
+                            let event = if *kind == "down" { InputEvent::KeyDown(key) } else { InputEvent::KeyUp(key) };
+                            movie.inputs.push((frame, event));
+                        }
+                        "cycles" => {
+                            let cycles = value.parse().map_err(|_| invalid())?;
+                            movie.inputs.push((frame, InputEvent::Cycles(cycles)));
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        // Movies without an `ips` line can't be replayed at the speed they were recorded at
+        if movie.ips == 0 {
+            return Err(Error::InvalidMovie { line: 1 });
+        }
+
+        Ok(movie)
+    }
+}
+
+pub struct Recorder {
+    movie: Movie,
+    cycles: Option<u32>,
+}
+
+impl Recorder {
+    pub fn new(seed: u64, program: &[u8], quirks: Quirks, ips: u32) -> Self {
+        Self {
+            movie: Movie {
+                seed,
+                rom_hash: rom_hash(program),
+                quirks,
+                ips,
+                frames: 0,
+                inputs: Vec::new(),
+                checksums: Vec::new(),
+            },
+            cycles: None,
+        }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.movie.frames
+    }
+
+    pub fn keydown(&mut self, key: u8) {
+        self.movie.inputs.push((self.movie.frames, InputEvent::KeyDown(key)));
+    }
+
+    pub fn keyup(&mut self, key: u8) {
+        self.movie.inputs.push((self.movie.frames, InputEvent::KeyUp(key)));
+    }
+
+    pub fn end_frame<R: RandomSource>(&mut self, emulator: &Emulator<R>, cycles: u32) {
+        if self.cycles != Some(cycles) {
+            self.cycles = Some(cycles);
+            self.movie.inputs.push((self.movie.frames, InputEvent::Cycles(cycles)));
+        }
+
+        self.movie.frames += 1;
+
+        if self.movie.frames.is_multiple_of(CHECKSUM_INTERVAL) {
+            self.movie.checksums.push((self.movie.frames, emulator.checksum()));
+        }
+    }
+
+    /// The movie recorded so far, which keeps growing until the recorder is finished
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+pub struct Player {
+    movie: Movie,
+    frame: u64,
+    cycles: u32,
+    next_input: usize,
+    next_checksum: usize,
+}
+
+impl Player {
+    pub fn new(movie: Movie) -> Self {
+        Self {
+            movie,
+            frame: 0,
+            cycles: 0,
+            next_input: 0,
+            next_checksum: 0,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.movie.seed
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.movie.quirks
+    }
+
+    pub fn ips(&self) -> u32 {
+        self.movie.ips
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    pub fn run_frame<R: RandomSource>(&mut self, emulator: &mut Emulator<R>) -> Result<()> {
+        self.run_frame_with(emulator, |emulator, cycles| emulator.cycle(cycles))
+    }
+
+    /// Runs the next frame with `cycle` instead of `Emulator::cycle`, to replay through a profiler or coverage recorder
+    pub fn run_frame_with<R, F>(&mut self, emulator: &mut Emulator<R>, mut cycle: F) -> Result<()>
+    where
+        R: RandomSource,
+        F: FnMut(&mut Emulator<R>, u32) -> Result<()>,
+    {
+        while let Some((frame, event)) = self.movie.inputs.get(self.next_input) {
+            if *frame > self.frame {
+                break;
+            }
+
+            match *event {
+                InputEvent::KeyDown(key) => emulator.keydown(key),
+                InputEvent::KeyUp(key) => emulator.keyup(key),
+                InputEvent::Cycles(cycles) => self.cycles = cycles,
+            }
+
+            self.next_input += 1;
+        }
+
+        emulator.time_step();
+        cycle(emulator, self.cycles)?;
+        self.frame += 1;
+
+        if let Some((frame, checksum)) = self.movie.checksums.get(self.next_checksum) {
+            if *frame == self.frame {
+                self.next_checksum += 1;
+
+                if *checksum != emulator.checksum() {
+                    return Err(Error::Desync { frame: self.frame });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XorShift;
+
+    // Draws a random digit whenever key 5 is held, forever.
+    const PROGRAM: [u8; 16] = [
+        0x60, 0x05, // LD V0, 5
+        0xE0, 0xA1, // SKNP V0
+        0x12, 0x08, // JP 0x208
+        0x12, 0x00, // JP 0x200
+        0xC1, 0x0F, // RND V1, 0x0F
+        0xF1, 0x29, // LD F, V1
+        0xD2, 0x25, // DRW V2, V2, 5
+        0x12, 0x00, // JP 0x200
+    ];
+
+    fn seeded(seed: u64) -> Emulator<XorShift> {
+        let mut emulator = Emulator::with_seed(seed);
+        emulator.set_quirks(Quirks::VIP);
+        emulator.reset();
+        emulator.load_program(&PROGRAM);
+        emulator
+    }
+
+    fn record(seed: u64, frames: u64) -> Result<Movie> {
+        let mut emulator = seeded(seed);
+        let mut recorder = Recorder::new(seed, &PROGRAM, emulator.quirks(), 600);
+
+        for frame in 0..frames {
+            if frame % 20 == 3 {
+                recorder.keydown(5);
+                emulator.keydown(5);
+            } else if frame % 20 == 9 {
+                recorder.keyup(5);
+                emulator.keyup(5);
+            }
+
+            emulator.time_step();
+            emulator.cycle(10)?;
+            recorder.end_frame(&emulator, 10);
+        }
+
+        Ok(recorder.finish())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let movie = record(0xC0FFEE, 130)?;
+        let parsed: Movie = movie.to_string().parse()?;
+
+        assert_eq!(parsed, movie);
+        assert_eq!((parsed.quirks, parsed.ips), (Quirks::VIP, 600));
+        assert_eq!(parsed.checksums.len(), 2);
+        assert!(parsed.matches_program(&PROGRAM));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay() -> Result<()> {
+        let movie = record(42, 200)?;
+        let mut player = Player::new(movie);
+        let mut emulator = seeded(player.seed());
+
+        while !player.finished() {
+            player.run_frame(&mut emulator)?;
+        }
+
+        assert_eq!(player.frame(), 200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_desync() -> Result<()> {
+        let movie = record(42, 200)?;
+        let mut player = Player::new(movie);
+        let mut emulator = seeded(7);
+
+        let result = loop {
+            if let Err(e) = player.run_frame(&mut emulator) {
+                break Err(e);
+            }
+
+            if player.finished() {
+                break Ok(());
+            }
+        };
+
+        assert!(matches!(result, Err(Error::Desync { frame: 60 })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!("hello".parse::<Movie>(), Err(Error::InvalidMovie { line: 1 })));
+
+        let movie = format!("{}\nseed 01\n12 down 1f\n", MAGIC);
+        assert!(matches!(movie.parse::<Movie>(), Err(Error::InvalidMovie { line: 3 })));
+
+        let movie = format!("{}\nseed 01\nquirks shift,bogus\n", MAGIC);
+        assert!(matches!(movie.parse::<Movie>(), Err(Error::InvalidMovie { line: 3 })));
+
+        let movie = format!("{}\nseed 01\nframes 0\n", MAGIC);
+        assert!(matches!(movie.parse::<Movie>(), Err(Error::InvalidMovie { line: 1 })));
+    }
+}
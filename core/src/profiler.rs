@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{Emulator, Instruction, RandomSource, Result};
+use crate::emulator::PROGRAM_OFFSET;
+
+const MEMORY_SIZE: usize = 0x1000;
+
+/// Instructions executed in a subroutine. Inclusive counts include the subroutines it calls, exclusive counts don't.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Function {
+    pub calls: u64,
+    pub inclusive: u64,
+    pub exclusive: u64,
+}
+
+/// Counts the instructions executed per address and per kind, and attributes them to subroutines by following `CALL`
+/// and `RET`. Profiling is opt-in: the emulator only pays for it when it's cycled through a profiler.
+pub struct Profiler {
+    hits: Vec<u64>,
+    kinds: BTreeMap<&'static str, u64>,
+    functions: BTreeMap<u16, Function>,
+    stacks: HashMap<Vec<u16>, u64>,
+    frames: Vec<u16>,
+    total: u64,
+}
+
+impl Profiler {
+    /// Creates a profiler for a program starting at `entry`, which is the root of every call stack
+    pub fn new(entry: u16) -> Self {
+        let mut functions = BTreeMap::new();
+        functions.insert(entry, Function { calls: 1, ..Function::default() });
+
+        Self {
+            hits: vec![0; MEMORY_SIZE],
+            kinds: BTreeMap::new(),
+            functions,
+            stacks: HashMap::new(),
+            frames: vec![entry],
+            total: 0,
+        }
+    }
+
+    pub fn cycle<R: RandomSource>(&mut self, emulator: &mut Emulator<R>, times: u32) -> Result<()> {
+        for _ in 0..times {
+            // Follows returns, and resets of the emulator between calls
+            self.frames.truncate(emulator.call_stack().len() + 1);
+
+            let pc = emulator.registers().pc;
+            let opcode = u16::from_be_bytes([
+                emulator.memory[pc as usize % MEMORY_SIZE],
+                emulator.memory[(pc as usize + 1) % MEMORY_SIZE],
+            ]);
+
+            let instruction = Instruction::try_from(opcode)?;
+            emulator.cycle(1)?;
+            self.record(pc, &instruction);
+
+            if let Instruction::CALL(addr) = instruction {
+                self.frames.push(addr as u16);
+                self.functions.entry(addr as u16).or_default().calls += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executions of each address
+    pub fn hits(&self) -> &[u64] {
+        &self.hits
+    }
+
+    /// Executions of each instruction kind, by name
+    pub fn kinds(&self) -> &BTreeMap<&'static str, u64> {
+        &self.kinds
+    }
+
+    /// Subroutines by entry address, including the root of the program
+    pub fn functions(&self) -> &BTreeMap<u16, Function> {
+        &self.functions
+    }
+
+    /// Instructions executed with each call stack active, from the root to the innermost subroutine
+    pub fn stacks(&self) -> impl Iterator<Item = (&[u16], u64)> {
+        self.stacks.iter().map(|(stack, instructions)| (stack.as_slice(), *instructions))
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    fn record(&mut self, pc: u16, instruction: &Instruction) {
+        self.hits[pc as usize % MEMORY_SIZE] += 1;
+        *self.kinds.entry(instruction.name()).or_default() += 1;
+        self.total += 1;
+
+        for (i, addr) in self.frames.iter().enumerate() {
+            let function = self.functions.entry(*addr).or_default();
+
+            // Recursive calls only count once towards the inclusive instructions
+            if !self.frames[..i].contains(addr) {
+                function.inclusive += 1;
+            }
+
+            if i == self.frames.len() - 1 {
+                function.exclusive += 1;
+            }
+        }
+
+        match self.stacks.get_mut(self.frames.as_slice()) {
+            Some(instructions) => *instructions += 1,
+            None => {
+                self.stacks.insert(self.frames.clone(), 1);
+            }
+        }
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new(PROGRAM_OFFSET as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XorShift;
+
+    // 200: LD V0, 5; CALL 208; ADD V0, 1; JP 204
+    // 208: LD V1, 7; CALL 20E; RET
+    // 20E: RET
+    const PROGRAM: [u8; 16] = [
+        0x60, 0x05, 0x22, 0x08, 0x70, 0x01, 0x12, 0x04,
+        0x61, 0x07, 0x22, 0x0E, 0x00, 0xEE, 0x00, 0xEE,
+    ];
+
+    fn profile(cycles: u32) -> Result<Profiler> {
+        let mut emulator = Emulator::<XorShift>::with_seed(0);
+        emulator.load_program(&PROGRAM);
+
+        let mut profiler = Profiler::default();
+        profiler.cycle(&mut emulator, cycles)?;
+        Ok(profiler)
+    }
+
+    #[test]
+    fn test_hits_and_kinds() -> Result<()> {
+        let profiler = profile(10)?;
+        assert_eq!(profiler.total(), 10);
+        assert_eq!(profiler.hits()[0x200..0x210], [1, 0, 1, 0, 2, 0, 2, 0, 1, 0, 1, 0, 1, 0, 1, 0]);
+
+        assert_eq!(profiler.kinds()["ADDVxKK"], 2);
+        assert_eq!(profiler.kinds()["RET"], 2);
+        assert_eq!(profiler.kinds().values().sum::<u64>(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_functions() -> Result<()> {
+        let profiler = profile(10)?;
+        let functions = profiler.functions();
+
+        assert_eq!(functions[&0x200], Function { calls: 1, inclusive: 10, exclusive: 6 });
+        assert_eq!(functions[&0x208], Function { calls: 1, inclusive: 4, exclusive: 3 });
+        assert_eq!(functions[&0x20E], Function { calls: 1, inclusive: 1, exclusive: 1 });
+        Ok(())
+    }
+
+    #[test]
+    fn test_stacks() -> Result<()> {
+        let profiler = profile(10)?;
+        let mut stacks: Vec<_> = profiler.stacks().collect();
+        stacks.sort();
+
+        assert_eq!(stacks, [(&[0x200][..], 6), (&[0x200, 0x208][..], 3), (&[0x200, 0x208, 0x20E][..], 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recursion() -> Result<()> {
+        // 200: CALL 202; 202: CALL 202
+        let mut emulator = Emulator::<XorShift>::with_seed(0);
+        emulator.load_program(&[0x22, 0x02, 0x22, 0x02]);
+
+        let mut profiler = Profiler::default();
+        profiler.cycle(&mut emulator, 4)?;
+        assert_eq!(profiler.functions()[&0x202], Function { calls: 4, inclusive: 3, exclusive: 3 });
+
+        emulator.reset();
+        emulator.load_program(&[0x22, 0x02, 0x22, 0x02]);
+        profiler.cycle(&mut emulator, 1)?;
+        assert_eq!(profiler.functions()[&0x200].exclusive, 2);
+        assert_eq!(profiler.functions()[&0x202].exclusive, 3);
+        Ok(())
+    }
+}
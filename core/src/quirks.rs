@@ -0,0 +1,170 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6` and `8XYE` shift VY into VX instead of shifting VX in place
+    pub shift_vy: bool,
+    /// `FX55` and `FX65` leave I pointing past the last register
+    pub load_store_i: bool,
+    /// `BNNN` jumps to NNN + VX instead of NNN + V0
+    pub jump_vx: bool,
+    /// `8XY1`, `8XY2` and `8XY3` reset VF to 0
+    pub vf_reset: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping around
+    pub clip: bool,
+}
+
+impl Quirks {
+    pub const MODERN: Self = Self {
+        shift_vy: false,
+        load_store_i: false,
+        jump_vx: false,
+        vf_reset: false,
+        clip: true,
+    };
+
+    pub const VIP: Self = Self {
+        shift_vy: true,
+        load_store_i: true,
+        jump_vx: false,
+        vf_reset: true,
+        clip: true,
+    };
+
+    pub const SCHIP: Self = Self {
+        shift_vy: false,
+        load_store_i: false,
+        jump_vx: true,
+        vf_reset: false,
+        clip: true,
+    };
+
+    /// Applies a comma-separated list of quirk names, each optionally prefixed with `no-` to disable it
+    pub fn apply(&mut self, list: &str) -> Result<()> {
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let (name, enabled) = match name.strip_prefix("no-") {
+                Some(name) => (name, false),
+                None => (name, true),
+            };
+
+            let quirk = match name {
+                "shift" => &mut self.shift_vy,
+                "load-store" => &mut self.load_store_i,
+                "jump" => &mut self.jump_vx,
+                "vf-reset" => &mut self.vf_reset,
+                "clip" => &mut self.clip,
+                _ => return Err(Error::UnknownQuirk { name: name.to_string() }),
+            };
+
+            *quirk = enabled;
+        }
+
+        Ok(())
+    }
+}
+
+/// Every quirk by name, prefixed with `no-` when it's disabled, in the format `Quirks::apply` reads
+impl Display for Quirks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let quirks = [
+            ("shift", self.shift_vy),
+            ("load-store", self.load_store_i),
+            ("jump", self.jump_vx),
+            ("vf-reset", self.vf_reset),
+            ("clip", self.clip),
+        ];
+
+        let names: Vec<String> = quirks
+            .iter()
+            .map(|(name, enabled)| if *enabled { name.to_string() } else { format!("no-{}", name) })
+            .collect();
+
+        write!(f, "{}", names.join(","))
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::MODERN
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    #[default]
+    Modern,
+    Vip,
+    Schip,
+}
+
+impl Platform {
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Self::Modern => Quirks::MODERN,
+            Self::Vip => Quirks::VIP,
+            Self::Schip => Quirks::SCHIP,
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Modern => write!(f, "modern"),
+            Self::Vip => write!(f, "vip"),
+            Self::Schip => write!(f, "schip"),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "modern" => Ok(Self::Modern),
+            "vip" | "chip-8" | "chip8" => Ok(Self::Vip),
+            "schip" | "superchip" => Ok(Self::Schip),
+            _ => Err(Error::UnknownPlatform { name: s.to_string() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform() -> Result<()> {
+        assert_eq!("VIP".parse::<Platform>()?.quirks(), Quirks::VIP);
+        assert_eq!("superchip".parse::<Platform>()?, Platform::Schip);
+        assert!("xo-chip".parse::<Platform>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply() -> Result<()> {
+        let mut quirks = Quirks::VIP;
+        quirks.apply("jump, no-shift,no-clip")?;
+
+        assert!(quirks.jump_vx);
+        assert!(!quirks.shift_vy);
+        assert!(!quirks.clip);
+        assert!(quirks.load_store_i);
+        assert!(quirks.apply("bogus").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() -> Result<()> {
+        assert_eq!(Quirks::VIP.to_string(), "shift,load-store,no-jump,vf-reset,clip");
+
+        let mut quirks = Quirks::MODERN;
+        quirks.apply(&Quirks::SCHIP.to_string())?;
+        assert_eq!(quirks, Quirks::SCHIP);
+        Ok(())
+    }
+}
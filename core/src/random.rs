@@ -0,0 +1,123 @@
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+}
+
+impl<F: FnMut() -> u8> RandomSource for F {
+    fn next_byte(&mut self) -> u8 {
+        self()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XorShift {
+    seed: u64,
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            state: Self::initial_state(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(seed);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Scrambles the seed with SplitMix64 so that small or similar seeds still
+    // produce unrelated sequences, and so the state is never zero.
+    fn initial_state(seed: u64) -> u64 {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        if z == 0 { 1 } else { z }
+    }
+}
+
+impl RandomSource for XorShift {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed() {
+        let mut a = XorShift::new(1234);
+        let mut b = XorShift::new(1234);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_byte(), b.next_byte());
+        }
+    }
+
+    #[test]
+    fn test_different_seed() {
+        let mut a = XorShift::new(1);
+        let mut b = XorShift::new(2);
+
+        let a: Vec<u8> = (0..16).map(|_| a.next_byte()).collect();
+        let b: Vec<u8> = (0..16).map(|_| b.next_byte()).collect();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_zero_seed() {
+        let mut rng = XorShift::new(0);
+        assert!((0..16).any(|_| rng.next_u64() != 0));
+    }
+
+    #[test]
+    fn test_reseed() {
+        let mut rng = XorShift::new(99);
+        let first: Vec<u8> = (0..8).map(|_| rng.next_byte()).collect();
+
+        rng.reseed(99);
+        let second: Vec<u8> = (0..8).map(|_| rng.next_byte()).collect();
+
+        assert_eq!(first, second);
+        assert_eq!(rng.seed(), 99);
+    }
+
+    #[test]
+    fn test_distribution() {
+        let mut rng = XorShift::new(0xDEAD_BEEF);
+        let mut counts = [0u32; 256];
+
+        for _ in 0..256 * 100 {
+            counts[rng.next_byte() as usize] += 1;
+        }
+
+        assert!(counts.iter().all(|c| (50..=150).contains(c)));
+    }
+
+    #[test]
+    fn test_closure() {
+        let mut value = 0;
+        let mut rng = || {
+            value += 1;
+            value
+        };
+
+        assert_eq!(rng.next_byte(), 1);
+        assert_eq!(rng.next_byte(), 2);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{Error, Result};
 
+#[derive(Clone)]
 pub struct Stack<const SIZE: usize> {
     sp: usize,
     data: [usize; SIZE],
@@ -32,6 +33,14 @@ impl<const SIZE: usize> Stack<SIZE> {
         Ok(self.data[self.sp])
     }
 
+    pub fn len(&self) -> usize {
+        self.sp
+    }
+
+    pub fn as_slice(&self) -> &[usize] {
+        &self.data[..self.sp]
+    }
+
     pub fn reset(&mut self) {
         self.sp = 0;
         self.data.fill(0);
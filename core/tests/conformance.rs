@@ -0,0 +1,172 @@
+//! Runs the test ROMs in `tests/roms` headlessly under every platform profile and compares the final
+//! display with the golden images in `tests/golden`. Run with `UPDATE_GOLDEN=1` to rewrite the golden images.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use core::{Display, Emulator, Platform};
+
+const CYCLES_PER_FRAME: u32 = 10;
+const PLATFORMS: [Platform; 3] = [Platform::Modern, Platform::Vip, Platform::Schip];
+
+struct Case {
+    rom: &'static str,
+    frames: u32,
+    /// Keys pressed (`true`) or released (`false`) at the start of a frame
+    input: &'static [(u32, u8, bool)],
+}
+
+fn dir(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join(name)
+}
+
+fn run(case: &Case, platform: Platform) -> Result<Display, String> {
+    let path = dir("roms").join(format!("{}.ch8", case.rom));
+    let program = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let mut emulator = Emulator::with_seed(0);
+    emulator.set_quirks(platform.quirks());
+    emulator.reset();
+    emulator.load_program(&program);
+
+    for frame in 0..case.frames {
+        for &(_, key, pressed) in case.input.iter().filter(|(at, _, _)| *at == frame) {
+            if pressed {
+                emulator.keydown(key);
+            } else {
+                emulator.keyup(key);
+            }
+        }
+
+        emulator.time_step();
+        emulator.cycle(CYCLES_PER_FRAME).map_err(|e| format!("frame {}: {}", frame, e))?;
+    }
+
+    Ok(emulator.display)
+}
+
+fn render(display: &Display) -> String {
+    let mut out = String::new();
+
+    for (x, _, on) in display.pixels() {
+        out.push(if on { '#' } else { '.' });
+
+        if x == Display::WIDTH - 1 {
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// The screen of a self-checking ROM in `tests/roms` after `checks` passing checks: one tick per check, 12 per row. It's
+/// drawn here rather than by the emulator, so the golden images of these ROMs can't hide a bug.
+fn ticks(checks: usize) -> String {
+    const TICK: [u8; 4] = [0x08, 0x10, 0xA0, 0x40];
+    let mut rows = vec![vec!['.'; Display::WIDTH]; Display::HEIGHT];
+
+    for check in 0..checks {
+        let (x, y) = (1 + check % 12 * 5, 1 + check / 12 * 5);
+
+        for (dy, byte) in TICK.iter().enumerate() {
+            for dx in (0..8).filter(|dx| byte & (0x80 >> dx) != 0) {
+                rows[y + dy][x + dx] = '#';
+            }
+        }
+    }
+
+    rows.into_iter().map(|row| row.into_iter().chain(['\n']).collect::<String>()).collect()
+}
+
+fn diff(expected: &str, actual: &str) -> String {
+    let mut out = format!("{:>4}{:<66}actual\n", "", "expected");
+
+    for (y, (expected, actual)) in expected.lines().zip(actual.lines()).enumerate() {
+        let marker = if expected == actual { "" } else { " <" };
+        out.push_str(&format!("{:>2}  {}  {}{}\n", y, expected, actual, marker));
+    }
+
+    out
+}
+
+fn check(case: &Case) {
+    let golden = dir("golden");
+    let mut actual = Vec::new();
+
+    for platform in PLATFORMS {
+        match run(case, platform) {
+            Ok(display) => actual.push((platform, render(&display))),
+            Err(e) => panic!("{} ({}): {}", case.rom, platform, e),
+        }
+    }
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        let shared = actual.iter().all(|(_, image)| *image == actual[0].1);
+
+        for (platform, image) in &actual {
+            let path = golden.join(format!("{}.{}.txt", case.rom, platform));
+
+            if shared {
+                let _ = fs::remove_file(path);
+            } else {
+                fs::write(path, image).unwrap();
+            }
+        }
+
+        if shared {
+            fs::write(golden.join(format!("{}.txt", case.rom)), &actual[0].1).unwrap();
+        }
+
+        return;
+    }
+
+    let mut failures = Vec::new();
+
+    for (platform, image) in &actual {
+        let specific = golden.join(format!("{}.{}.txt", case.rom, platform));
+        let path = if specific.exists() { specific } else { golden.join(format!("{}.txt", case.rom)) };
+
+        let expected = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+        if expected != *image {
+            failures.push(format!("{} ({}) differs from {}:\n{}", case.rom, platform, path.display(), diff(&expected, image)));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_font() {
+    check(&Case { rom: "font", frames: 10, input: &[] });
+}
+
+#[test]
+fn test_opcodes() {
+    check(&Case { rom: "opcodes", frames: 30, input: &[] });
+}
+
+#[test]
+fn test_flags() {
+    check(&Case { rom: "flags", frames: 40, input: &[] });
+}
+
+#[test]
+fn test_quirks() {
+    check(&Case { rom: "quirks", frames: 10, input: &[] });
+}
+
+#[test]
+fn test_keypad() {
+    check(&Case { rom: "keypad", frames: 30, input: &[(2, 0xA, true), (10, 0x5, true), (20, 0x5, false)] });
+}
+
+#[test]
+fn test_self_checking_goldens() {
+    for (rom, checks) in [("opcodes", 17), ("flags", 26)] {
+        let path = dir("golden").join(format!("{}.txt", rom));
+        let golden = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert!(golden == ticks(checks), "{} isn't {} ticks:\n{}", path.display(), checks, diff(&ticks(checks), &golden));
+    }
+}